@@ -0,0 +1,105 @@
+## Errors and status
+
+failed-fetch = Failed to fetch media, you foking donkey.
+    .clean = Sorry, I could not fetch that media.
+job-cancelled = The bot is restarting and your download was cancelled. Send the link again in a minute.
+rate-limited = Easy there, you are sending links faster than we can fetch them. Try again in a bit.
+no-media = No supported media found
+shared-by = Shared by { $user }
+shared-by-with-text = Shared by { $user }: { $text }
+caption-source = Source
+
+## Inline mode
+
+inline-pending = Still fetching that one. Type the link again in a few seconds.
+inline-pending-title = Downloading…
+inline-send-video = Send video
+
+## Commands
+
+chat-admins-only = Only chat admins can change this.
+bot-admins-only = Only bot admins can do this.
+save-failed = Failed to save the setting.
+
+curse-usage = Usage: /curse [generated] [@user|optout|optin], or reply to a message with /curse
+curse-anonymous = Anonymous admins cannot opt out.
+curse-opted-out = You will no longer be targeted by /curse.
+curse-opted-in = You can be targeted by /curse again.
+curse-target-opted-out = { $user } does not want to be targeted.
+curse-cooldown = Give { $user } a break. Try again in { $minutes } min.
+curse-generate-failed = No generated comment this time. The generator may be off (COMMENT_GENERATOR).
+
+replace-status-on = Replace mode is on.
+replace-status-off = Replace mode is off.
+replace-usage = Usage: /replace on|off
+replace-enabled = Replace mode enabled. Give me the "Delete messages" admin right so I can remove the original links.
+replace-disabled = Replace mode disabled.
+
+clean-status-on = Clean mode is on.
+clean-status-off = Clean mode is off.
+clean-usage = Usage: /clean on|off
+clean-enabled = Clean mode enabled. Messages stay polite and profane comments are skipped.
+clean-disabled = Clean mode disabled.
+
+packs-status =
+    Available packs: { $available }
+    This chat uses: { $selected }
+packs-all = all
+packs-unknown = Unknown packs: { $packs }
+packs-set-all = This chat now uses all packs.
+packs-set = This chat now uses: { $packs }
+
+language-status = This chat uses { $language }. Available: { $available }
+language-auto = the sender's language
+language-unknown = Unknown language: { $language }. Available: { $available }
+language-set = Language set to { $language }.
+language-reset = Now using the sender's language.
+
+reload-done = Loaded { $count } comments.
+reload-failed = Reload failed, keeping the current set: { $error }
+
+comment-anonymous = Anonymous admins cannot add comments.
+comment-add-usage = Usage: /addcomment text
+comment-added = Added comment #{ $id }.
+comment-add-failed = Cannot add comment: { $error }
+comment-delete-usage = Usage: /delcomment id (see /listcomments)
+comment-deleted = Deleted comment #{ $id }.
+comment-not-found = No added comment #{ $id }. Comments from files cannot be deleted here.
+comment-save-failed = Failed to save the change.
+comment-reload-pending = { $reply } It takes effect after the next successful reload: { $error }
+
+list-empty = No comments found.
+list-header = { $count } comments, page { $page }/{ $pages }:
+list-header-search = { $count } comments matching "{ $query }", page { $page }/{ $pages }:
+list-by = (by { $author }, { $date })
+list-prev = ‹ Prev
+list-next = Next ›
+
+## /cookies
+
+cookies-admins-only = Only users listed in ADMIN_USER_IDS can replace cookies.
+cookies-usage = Usage: send a cookies.txt file with /cookies platform [account] as its caption, or reply to one with it. The account is the file's number in the platform's cookie list (default 1). Platforms: { $platforms }
+cookies-not-configured = No cookie file is configured for { $platform }. Set { $variable } first.
+cookies-unknown-account = { $platform } has { $count } cookie files; pick an account from 1 to { $count }.
+cookies-too-large = This file is too large to be a cookie file.
+cookies-download-failed = Failed to download the file.
+cookies-invalid = Cookies not replaced: { $error }
+cookies-replaced = Replaced the { $platform } cookies ({ $count } cookies, { $foreign } for other sites). Session: { $sessions }
+cookies-expiry-at = { $name } until { $date }
+cookies-expiry-session = { $name } until the browser session ends
+cookies-expiry-expired = { $name } expired on { $date }
+
+## /help
+
+help-header = Commands:
+help-help = Display this text
+help-curse = Send a random comment, aimed at a reply or user: /curse [generated] [@user|optout|optin]
+help-replace = Delete link messages and repost the media with credit: /replace on|off
+help-clean = Family-friendly messages and comments: /clean on|off
+help-packs = Choose comment packs: /packs [name ...|all]
+help-language = Choose the bot language: /language [code|auto]
+help-reloadcomments = Reload comments and packs from disk (bot admins only)
+help-addcomment = Add a comment (bot admins only): /addcomment text
+help-delcomment = Delete an added comment (bot admins only): /delcomment id
+help-listcomments = List or search comments (bot admins only): /listcomments [search]
+help-cookies = Replace a platform's cookie file (admin users only): /cookies platform [account]
@@ -0,0 +1,105 @@
+## Kļūdas un statuss
+
+failed-fetch = Neizdevās ielādēt medijus, tu sasodītais ēzeli.
+    .clean = Atvainojiet, neizdevās ielādēt šos medijus.
+job-cancelled = Bots tiek restartēts, un tava lejupielāde tika atcelta. Pēc minūtes nosūti saiti vēlreiz.
+rate-limited = Lēnāk, tu sūti saites ātrāk, nekā mēs spējam tās ielādēt. Pamēģini vēlāk.
+no-media = Netika atrasts neviens atbalstīts medijs
+shared-by = Kopīgoja { $user }
+shared-by-with-text = Kopīgoja { $user }: { $text }
+caption-source = Avots
+
+## Iekļautais režīms
+
+inline-pending = Šī saite vēl tiek ielādēta. Pēc dažām sekundēm ieraksti to vēlreiz.
+inline-pending-title = Lejupielādē…
+inline-send-video = Sūtīt video
+
+## Komandas
+
+chat-admins-only = Šo var mainīt tikai čata administratori.
+bot-admins-only = To var darīt tikai bota administratori.
+save-failed = Neizdevās saglabāt iestatījumu.
+
+curse-usage = Lietojums: /curse [generated] [@lietotājs|optout|optin] vai atbildi uz ziņu ar /curse
+curse-anonymous = Anonīmie administratori nevar atteikties.
+curse-opted-out = Tevi vairs nevarēs mērķēt ar /curse.
+curse-opted-in = Tevi atkal var mērķēt ar /curse.
+curse-target-opted-out = { $user } nevēlas tikt mērķēts.
+curse-cooldown = Dod { $user } atelpu. Mēģini vēlreiz pēc { $minutes } min.
+curse-generate-failed = Šoreiz ģenerēts komentārs neizdevās. Iespējams, ģenerators ir izslēgts (COMMENT_GENERATOR).
+
+replace-status-on = Aizstāšanas režīms ir ieslēgts.
+replace-status-off = Aizstāšanas režīms ir izslēgts.
+replace-usage = Lietojums: /replace on|off
+replace-enabled = Aizstāšanas režīms ieslēgts. Piešķir man administratora tiesības "Dzēst ziņas", lai varu noņemt sākotnējās saites.
+replace-disabled = Aizstāšanas režīms izslēgts.
+
+clean-status-on = Pieklājīgais režīms ir ieslēgts.
+clean-status-off = Pieklājīgais režīms ir izslēgts.
+clean-usage = Lietojums: /clean on|off
+clean-enabled = Pieklājīgais režīms ieslēgts. Ziņas būs pieklājīgas, un rupji komentāri tiks izlaisti.
+clean-disabled = Pieklājīgais režīms izslēgts.
+
+packs-status =
+    Pieejamās pakas: { $available }
+    Šajā čatā izmanto: { $selected }
+packs-all = visas
+packs-unknown = Nezināmas pakas: { $packs }
+packs-set-all = Šis čats tagad izmanto visas pakas.
+packs-set = Šis čats tagad izmanto: { $packs }
+
+language-status = Šis čats izmanto: { $language }. Pieejamās valodas: { $available }
+language-auto = sūtītāja valodu
+language-unknown = Nezināma valoda: { $language }. Pieejamās valodas: { $available }
+language-set = Valoda nomainīta uz { $language }.
+language-reset = Tagad tiek izmantota sūtītāja valoda.
+
+reload-done = Ielādēti komentāri: { $count }.
+reload-failed = Pārlāde neizdevās, paturu pašreizējos komentārus: { $error }
+
+comment-anonymous = Anonīmi administratori nevar pievienot komentārus.
+comment-add-usage = Lietojums: /addcomment teksts
+comment-added = Pievienots komentārs #{ $id }.
+comment-add-failed = Nevar pievienot komentāru: { $error }
+comment-delete-usage = Lietojums: /delcomment id (skat. /listcomments)
+comment-deleted = Dzēsts komentārs #{ $id }.
+comment-not-found = Nav pievienota komentāra #{ $id }. Komentārus no failiem šeit dzēst nevar.
+comment-save-failed = Neizdevās saglabāt izmaiņas.
+comment-reload-pending = { $reply } Tas stāsies spēkā pēc nākamās veiksmīgās pārlādes: { $error }
+
+list-empty = Komentāri netika atrasti.
+list-header = Komentāri: { $count }, lapa { $page }/{ $pages }:
+list-header-search = Komentāri, kas atbilst "{ $query }": { $count }, lapa { $page }/{ $pages }:
+list-by = (pievienoja { $author }, { $date })
+list-prev = ‹ Iepriekšējā
+list-next = Nākamā ›
+
+## /cookies
+
+cookies-admins-only = Sīkdatnes var aizstāt tikai ADMIN_USER_IDS norādītie lietotāji.
+cookies-usage = Lietojums: nosūti cookies.txt failu ar parakstu /cookies platforma [konts] vai atbildi uz to ar šo komandu. Konts ir faila numurs platformas sīkdatņu sarakstā (pēc noklusējuma 1). Platformas: { $platforms }
+cookies-not-configured = Platformai { $platform } nav iestatīts sīkdatņu fails. Vispirms iestati { $variable }.
+cookies-unknown-account = Platformai { $platform } ir { $count } sīkdatņu faili; izvēlies kontu no 1 līdz { $count }.
+cookies-too-large = Šis fails ir pārāk liels, lai būtu sīkdatņu fails.
+cookies-download-failed = Neizdevās lejupielādēt failu.
+cookies-invalid = Sīkdatnes netika aizstātas: { $error }
+cookies-replaced = Platformas { $platform } sīkdatnes aizstātas ({ $count } sīkdatnes, { $foreign } citām vietnēm). Sesija: { $sessions }
+cookies-expiry-at = { $name } līdz { $date }
+cookies-expiry-session = { $name } līdz pārlūka sesijas beigām
+cookies-expiry-expired = { $name } beidzās { $date }
+
+## /help
+
+help-header = Komandas:
+help-help = Parādīt šo tekstu
+help-curse = Nosūtīt nejaušu komentāru, vērstu pret atbildes autoru vai lietotāju: /curse [generated] [@lietotājs|optout|optin]
+help-replace = Dzēst ziņas ar saitēm un pārpublicēt medijus ar autora norādi: /replace on|off
+help-clean = Ģimenei draudzīgas ziņas un komentāri: /clean on|off
+help-packs = Izvēlēties komentāru pakas: /packs [nosaukums ...|all]
+help-language = Izvēlēties bota valodu: /language [kods|auto]
+help-reloadcomments = Pārlādēt komentārus un pakas no diska (tikai bota administratoriem)
+help-addcomment = Pievienot komentāru (tikai bota administratoriem): /addcomment teksts
+help-delcomment = Dzēst pievienotu komentāru (tikai bota administratoriem): /delcomment id
+help-listcomments = Parādīt vai meklēt komentārus (tikai bota administratoriem): /listcomments [meklēt]
+help-cookies = Aizstāt platformas sīkdatņu failu (tikai administratoriem no ADMIN_USER_IDS): /cookies platforma [konts]
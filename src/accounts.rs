@@ -0,0 +1,206 @@
+use crate::{
+    config::{Config, global_config},
+    cookies::Platform,
+    error::Error,
+    monitor::is_login_required,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+static GLOBAL_ACCOUNTS: OnceLock<Accounts> = OnceLock::new();
+
+/// yt-dlp errors (lowercase) that mean the account is being rate limited.
+const RATE_LIMIT_ERRORS: &[&str] = &[
+    "rate-limit",
+    "rate limit",
+    "too many requests",
+    "http error 429",
+];
+
+/// A cookie file picked for one download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub platform: Platform,
+    /// Position in the platform's pool.
+    pub index: usize,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.path.file_name().unwrap_or(self.path.as_os_str());
+        write!(
+            f,
+            "{}#{} ({})",
+            self.platform.name(),
+            self.index + 1,
+            file.display()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    last_used: Option<Instant>,
+    cooldown_until: Option<Instant>,
+}
+
+/// Pools of cookie files (one per account) per platform. Each download gets
+/// the least recently used account that is not cooling down after a rate
+/// limit or login failure.
+#[derive(Debug)]
+pub struct Accounts {
+    pools: HashMap<Platform, Vec<PathBuf>>,
+    usage: Mutex<HashMap<(Platform, usize), Usage>>,
+    cooldown: Duration,
+}
+
+impl Accounts {
+    #[must_use]
+    pub fn new(pools: HashMap<Platform, Vec<PathBuf>>, cooldown: Duration) -> Self {
+        Self {
+            pools,
+            usage: Mutex::default(),
+            cooldown,
+        }
+    }
+
+    /// Pools from the `*_SESSION_COOKIE_PATH` settings.
+    #[must_use]
+    pub fn from_config(config: &Config) -> Self {
+        let pools = Platform::ALL
+            .into_iter()
+            .map(|p| (p, p.cookies_paths(config).to_vec()))
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect();
+        Self::new(pools, config.cookie_cooldown)
+    }
+
+    /// Pick the account for a download of `platform`, `None` without cookie
+    /// files. Accounts whose file is missing are skipped. When every account
+    /// is cooling down, the one that is free first is used anyway.
+    pub fn pick(&self, platform: Platform, now: Instant) -> Option<Account> {
+        let paths = self.pools.get(&platform)?;
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+        let available = (0..paths.len()).filter(|&i| paths[i].is_file());
+        let index = available.min_by_key(|&i| {
+            let u = usage.get(&(platform, i));
+            let cooling = u
+                .and_then(|u| u.cooldown_until)
+                .filter(|&until| until > now);
+            // available accounts first, least recently used (never used
+            // first), then the cooldown that ends first
+            (cooling, u.and_then(|u| u.last_used))
+        })?;
+        usage.entry((platform, index)).or_default().last_used = Some(now);
+        drop(usage);
+        Some(Account {
+            platform,
+            index,
+            path: paths[index].clone(),
+        })
+    }
+
+    /// Record the outcome of a download with `account`. Rate limit and login
+    /// failures put the account on cooldown.
+    pub fn finish(&self, account: &Account, error: Option<&Error>, now: Instant) {
+        let Some(error) = error.filter(|e| is_rate_limited(e) || is_login_required(e)) else {
+            return;
+        };
+        warn!(%account, cooldown = ?self.cooldown, "cookie account failed, cooling down: {error}");
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((account.platform, account.index))
+            .or_default()
+            .cooldown_until = Some(now + self.cooldown);
+    }
+
+    /// The account using the cookie file at `path`, if any.
+    #[must_use]
+    pub fn find(&self, path: &Path) -> Option<Account> {
+        self.pools.iter().find_map(|(&platform, paths)| {
+            let index = paths.iter().position(|p| p == path)?;
+            Some(Account {
+                platform,
+                index,
+                path: path.to_path_buf(),
+            })
+        })
+    }
+}
+
+/// Get the global account pools, built from the config on first use.
+#[must_use]
+pub fn global_accounts() -> &'static Accounts {
+    GLOBAL_ACCOUNTS.get_or_init(|| Accounts::from_config(global_config()))
+}
+
+/// Whether a failed download hit a rate limit.
+#[must_use]
+pub fn is_rate_limited(error: &Error) -> bool {
+    let text = error
+        .stderr()
+        .map_or_else(|| error.to_string(), str::to_owned)
+        .to_lowercase();
+    RATE_LIMIT_ERRORS
+        .iter()
+        .any(|pattern| text.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_skips_cooling_accounts() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = |name: &str| dir.path().join(name);
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(path(name), "").expect("create");
+        }
+        // d.txt is missing and never picked
+        let paths = ["a.txt", "b.txt", "c.txt", "d.txt"].map(path).to_vec();
+        let accounts = Accounts::new(
+            HashMap::from([(Platform::Instagram, paths)]),
+            Duration::from_mins(30),
+        );
+        let now = Instant::now();
+        let at = |secs| now + Duration::from_secs(secs);
+        let pick = |secs| {
+            accounts
+                .pick(Platform::Instagram, at(secs))
+                .expect("pool")
+                .index
+        };
+
+        assert_eq!([pick(0), pick(1), pick(2), pick(3)], [0, 1, 2, 0]);
+        assert!(accounts.pick(Platform::Twitter, now).is_none());
+
+        let limited = Error::ytdlp_failed(Some(1), "ERROR: HTTP Error 429: Too Many Requests");
+        let other = Error::ytdlp_failed(Some(1), "ERROR: Unsupported URL");
+        let b = accounts.find(&path("b.txt")).expect("in pool");
+        assert_eq!(b.to_string(), "instagram#2 (b.txt)");
+        accounts.finish(&b, Some(&limited), at(4));
+        let c = accounts.find(&path("c.txt")).expect("in pool");
+        accounts.finish(&c, Some(&other), at(4));
+
+        // b is cooling down, the others alternate
+        assert_eq!([pick(5), pick(6), pick(7)], [2, 0, 2]);
+        // all cooling: the one free first is used
+        let a = accounts.find(&path("a.txt")).expect("in pool");
+        let c = accounts.find(&path("c.txt")).expect("in pool");
+        accounts.finish(&a, Some(&limited), at(8));
+        accounts.finish(&c, Some(&limited), at(9));
+        assert_eq!(pick(10), 1);
+        // cooldown over
+        assert_eq!(pick(4 + 30 * 60), 1);
+        assert_eq!(pick(9 + 30 * 60), 0);
+    }
+}
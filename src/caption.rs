@@ -0,0 +1,386 @@
+use crate::{comments::global_comments, config::global_config, template::CaptionContext, tr};
+use teloxide::types::ParseMode;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Telegram's limit for photo and video captions.
+pub const MEDIA_CAPTION_LIMIT: usize = 1024;
+/// Telegram's limit for text messages.
+pub const MESSAGE_TEXT_LIMIT: usize = 4096;
+
+const SEPARATOR: &str = "\n\n";
+const ELLIPSIS: char = '…';
+/// Shortest comment kept before optional sections are dropped.
+const MIN_COMMENT: usize = 64;
+
+/// Markup captions are sent with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptionFormat {
+    #[default]
+    Plain,
+    Html,
+    MarkdownV2,
+}
+
+impl CaptionFormat {
+    /// Parse mode to send the rendered caption with.
+    #[must_use]
+    pub const fn parse_mode(self) -> Option<ParseMode> {
+        match self {
+            Self::Plain => None,
+            Self::Html => Some(ParseMode::Html),
+            Self::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        }
+    }
+
+    /// Escape `text` so it is shown as is.
+    #[must_use]
+    pub fn escape(self, text: &str) -> String {
+        match self {
+            Self::Plain => text.to_owned(),
+            Self::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+            Self::MarkdownV2 => escape_markdown(text, "_*[]()~`>#+-=|{}.!\\"),
+        }
+    }
+}
+
+/// A link to the original post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLink {
+    pub label: String,
+    pub url: String,
+}
+
+/// The sections of a caption, in output order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Caption {
+    /// Credit line (replace mode).
+    pub attribution: Option<String>,
+    pub comment: String,
+    /// Title, uploader and duration of the media.
+    pub metadata: Option<String>,
+    pub source: Option<SourceLink>,
+    /// Shown in italics.
+    pub disclaimer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Attribution,
+    Comment,
+    Metadata,
+    Source,
+    Disclaimer,
+}
+
+#[derive(Debug)]
+struct Part {
+    section: Section,
+    text: String,
+    style: Style,
+}
+
+#[derive(Debug)]
+enum Style {
+    Plain,
+    Italic,
+    Link(String),
+}
+
+impl Caption {
+    /// Caption for `comment` with the sections enabled in the config,
+    /// filled from `ctx`.
+    #[must_use]
+    pub fn compose(ctx: &CaptionContext, comment: String, attribution: Option<String>) -> Self {
+        let config = &global_config().caption;
+        let lang = ctx.lang.as_deref().unwrap_or_default();
+        Self {
+            attribution,
+            comment,
+            metadata: config.metadata.then(|| metadata(ctx)).flatten(),
+            source: ctx
+                .source
+                .clone()
+                .filter(|_| config.source_link)
+                .map(|url| SourceLink {
+                    label: tr!(lang, "caption-source"),
+                    url,
+                }),
+            disclaimer: config
+                .disclaimer
+                .then(|| global_comments().disclaimer.clone()),
+        }
+    }
+
+    /// Render with the configured format, at most `limit` characters long.
+    #[must_use]
+    pub fn render_configured(&self, limit: usize) -> String {
+        self.render(global_config().caption.format, limit)
+    }
+
+    /// Render as `format`, at most `limit` characters long as Telegram counts
+    /// them (UTF-16 code units of the text without markup).
+    ///
+    /// When too long the disclaimer, source link and metadata are dropped
+    /// until the rest fits with the comment cut to its minimum length, then
+    /// the comment and, if still needed, the attribution are shortened. Text
+    /// is only cut between grapheme clusters and never inside markup.
+    #[must_use]
+    pub fn render(&self, format: CaptionFormat, limit: usize) -> String {
+        let mut parts = self.parts(format);
+        for optional in [Section::Disclaimer, Section::Source, Section::Metadata] {
+            if min_length(&parts) <= limit {
+                break;
+            }
+            parts.retain(|p| p.section != optional);
+        }
+        shorten(&mut parts, Section::Comment, limit, MIN_COMMENT);
+        for section in [Section::Attribution, Section::Comment] {
+            shorten(&mut parts, section, limit, 0);
+        }
+
+        parts
+            .iter()
+            .filter(|p| !p.text.is_empty())
+            .map(|p| p.render(format))
+            .collect::<Vec<_>>()
+            .join(SEPARATOR)
+    }
+
+    fn parts(&self, format: CaptionFormat) -> Vec<Part> {
+        let plain = |section, text: &Option<String>| {
+            text.as_ref().map(|text| Part {
+                section,
+                text: text.clone(),
+                style: Style::Plain,
+            })
+        };
+        let source = self.source.as_ref().map(|link| match format {
+            CaptionFormat::Plain => Part {
+                section: Section::Source,
+                text: link.url.clone(),
+                style: Style::Plain,
+            },
+            CaptionFormat::Html | CaptionFormat::MarkdownV2 => Part {
+                section: Section::Source,
+                text: link.label.clone(),
+                style: Style::Link(link.url.clone()),
+            },
+        });
+        let disclaimer = self.disclaimer.as_ref().map(|text| Part {
+            section: Section::Disclaimer,
+            text: text.clone(),
+            style: Style::Italic,
+        });
+
+        [
+            plain(Section::Attribution, &self.attribution),
+            plain(Section::Comment, &Some(self.comment.clone())),
+            plain(Section::Metadata, &self.metadata),
+            source,
+            disclaimer,
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|p| !p.text.trim().is_empty())
+        .collect()
+    }
+}
+
+impl Part {
+    fn render(&self, format: CaptionFormat) -> String {
+        let text = format.escape(&self.text);
+        match (&self.style, format) {
+            (Style::Plain, _) | (Style::Italic | Style::Link(_), CaptionFormat::Plain) => text,
+            (Style::Italic, CaptionFormat::Html) => format!("<i>{text}</i>"),
+            (Style::Italic, CaptionFormat::MarkdownV2) => format!("_{text}_"),
+            (Style::Link(url), CaptionFormat::Html) => {
+                format!("<a href=\"{}\">{text}</a>", CaptionFormat::Html.escape(url))
+            }
+            (Style::Link(url), CaptionFormat::MarkdownV2) => {
+                format!("[{text}]({})", escape_markdown(url, ")\\"))
+            }
+        }
+    }
+}
+
+/// "Title — uploader (3:25)" from whatever `ctx` knows.
+fn metadata(ctx: &CaptionContext) -> Option<String> {
+    let names = [ctx.title.as_deref(), ctx.uploader.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" — ");
+    match (names.is_empty(), ctx.duration.as_deref()) {
+        (true, None) => None,
+        (true, Some(duration)) => Some(duration.to_owned()),
+        (false, None) => Some(names),
+        (false, Some(duration)) => Some(format!("{names} ({duration})")),
+    }
+}
+
+/// Visible length of the joined parts.
+fn length(parts: &[Part]) -> usize {
+    let separators = parts.len().saturating_sub(1) * utf16_len(SEPARATOR);
+    parts.iter().map(|p| utf16_len(&p.text)).sum::<usize>() + separators
+}
+
+/// Visible length of the joined parts with the comment cut to
+/// `MIN_COMMENT`.
+fn min_length(parts: &[Part]) -> usize {
+    let comment = parts
+        .iter()
+        .find(|p| p.section == Section::Comment)
+        .map_or(0, |p| utf16_len(&p.text));
+    length(parts) - comment + comment.min(MIN_COMMENT)
+}
+
+/// Shorten the `section` part until all parts fit `limit`, keeping at least
+/// `min` units of it.
+fn shorten(parts: &mut Vec<Part>, section: Section, limit: usize, min: usize) {
+    let by = length(parts).saturating_sub(limit);
+    if by == 0 {
+        return;
+    }
+    let Some(part) = parts.iter_mut().find(|p| p.section == section) else {
+        return;
+    };
+    let len = utf16_len(&part.text);
+    let target = len.saturating_sub(by).max(min.min(len));
+    part.text = truncate(&part.text, target);
+    if part.text.is_empty() {
+        parts.retain(|p| p.section != section);
+    }
+}
+
+/// Cut `text` to at most `max` UTF-16 units on a grapheme boundary, ending
+/// with an ellipsis when anything was removed.
+#[must_use]
+pub fn truncate(text: &str, max: usize) -> String {
+    if utf16_len(text) <= max {
+        return text.to_owned();
+    }
+    let budget = max.saturating_sub(ELLIPSIS.len_utf16());
+    let mut used = 0;
+    let mut kept = String::new();
+    for grapheme in text.graphemes(true) {
+        used += utf16_len(grapheme);
+        if used > budget {
+            break;
+        }
+        kept.push_str(grapheme);
+    }
+    let mut kept = kept.trim_end().to_owned();
+    if max > 0 {
+        kept.push(ELLIPSIS);
+    }
+    kept
+}
+
+/// Length of `text` as Telegram counts it, in UTF-16 code units.
+#[must_use]
+pub fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+fn escape_markdown(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caption(comment: &str) -> Caption {
+        Caption {
+            attribution: Some("Shared by @kris".into()),
+            comment: comment.into(),
+            metadata: Some("Lap 1 <crash> — F1 (1:05)".into()),
+            source: Some(SourceLink {
+                label: "Source".into(),
+                url: "https://example.com/a_(b)?x=1&y=2".into(),
+            }),
+            disclaimer: Some("(Roleplay.)".into()),
+        }
+    }
+
+    #[test]
+    fn renders_and_escapes_formats() {
+        let c = caption("Box, box! 5*2");
+        assert_eq!(
+            c.render(CaptionFormat::Plain, MEDIA_CAPTION_LIMIT),
+            "Shared by @kris\n\nBox, box! 5*2\n\nLap 1 <crash> — F1 (1:05)\n\nhttps://example.com/a_(b)?x=1&y=2\n\n(Roleplay.)"
+        );
+        assert_eq!(
+            c.render(CaptionFormat::Html, MEDIA_CAPTION_LIMIT),
+            "Shared by @kris\n\nBox, box! 5*2\n\nLap 1 &lt;crash&gt; — F1 (1:05)\n\n<a href=\"https://example.com/a_(b)?x=1&amp;y=2\">Source</a>\n\n<i>(Roleplay.)</i>"
+        );
+        assert_eq!(
+            c.render(CaptionFormat::MarkdownV2, MEDIA_CAPTION_LIMIT),
+            "Shared by @kris\n\nBox, box\\! 5\\*2\n\nLap 1 <crash\\> — F1 \\(1:05\\)\n\n[Source](https://example.com/a_(b\\)?x=1&y=2)\n\n_\\(Roleplay\\.\\)_"
+        );
+    }
+
+    #[test]
+    fn truncates_on_grapheme_boundaries() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let text = format!("ab{family}{family}");
+        assert_eq!(truncate(&text, 100), text);
+        // the family emoji is 8 UTF-16 units and must not be split
+        assert_eq!(truncate(&text, 12), format!("ab{family}…"));
+        assert_eq!(truncate(&text, 10), "ab…");
+        assert_eq!(truncate(&text, 0), "");
+    }
+
+    #[test]
+    fn fits_limits_dropping_optional_sections() {
+        let long = "word ".repeat(400);
+        let c = caption(&long);
+
+        let media = c.render(CaptionFormat::Html, MEDIA_CAPTION_LIMIT);
+        let text = c.render(CaptionFormat::Html, MESSAGE_TEXT_LIMIT);
+        assert!(media.contains("<i>(Roleplay.)</i>") && media.contains('…'));
+        assert!(!text.contains('…'));
+
+        let plain = c.render(CaptionFormat::Plain, MEDIA_CAPTION_LIMIT);
+        assert!(utf16_len(&plain) <= MEDIA_CAPTION_LIMIT);
+
+        // a long attribution pushes out optional sections, the comment keeps
+        // its minimum length
+        let crowded = Caption {
+            attribution: Some("a".repeat(MEDIA_CAPTION_LIMIT - 100)),
+            ..c
+        };
+        let rendered = crowded.render(CaptionFormat::Plain, MEDIA_CAPTION_LIMIT);
+        assert!(utf16_len(&rendered) <= MEDIA_CAPTION_LIMIT);
+        assert!(!rendered.contains("Roleplay") && !rendered.contains("https://"));
+        assert!(rendered.contains("word word"));
+    }
+
+    #[test]
+    fn long_metadata_does_not_cut_the_comment() {
+        let comment = "lap ".repeat(100);
+        let c = Caption {
+            metadata: Some("m".repeat(MEDIA_CAPTION_LIMIT - 20)),
+            ..caption(comment.trim_end())
+        };
+        let rendered = c.render(CaptionFormat::Plain, MEDIA_CAPTION_LIMIT);
+        assert!(utf16_len(&rendered) <= MEDIA_CAPTION_LIMIT);
+        // the optional sections go and the comment is not cut for them
+        assert_eq!(
+            rendered,
+            format!("Shared by @kris\n\n{}", comment.trim_end())
+        );
+    }
+}
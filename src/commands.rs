@@ -1,5 +1,50 @@
-use crate::comments::global_comments;
-use teloxide::{prelude::*, utils::command::BotCommands};
+use crate::{
+    caption::{Caption, MESSAGE_TEXT_LIMIT},
+    comments::global_comments,
+    config::global_config,
+    context::MessageContext,
+    cookies::{self, Expiry, Platform},
+    i18n::global_catalog,
+    monitor::global_monitor,
+    reload::reload_comments,
+    settings::{ChatSettings, global_settings},
+    store::{format_date, global_store},
+    targets::{Target, global_targets},
+    tr,
+};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use teloxide::{
+    net::Download,
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageEntityKind, MessageId, User},
+    utils::command::BotCommands,
+};
+use tracing::{error, info, warn};
+
+/// Comments shown per `/listcomments` page.
+const PAGE_SIZE: usize = 10;
+/// Longest comment text shown in a listing.
+const LIST_TEXT_LIMIT: usize = 120;
+/// Callback data prefix of the `/listcomments` page buttons.
+const LIST_CALLBACK_PREFIX: &str = "lc:";
+/// Telegram's limit on callback data.
+const CALLBACK_DATA_LIMIT: usize = 64;
+/// Largest accepted `/cookies` upload.
+const COOKIE_FILE_LIMIT: u32 = 1024 * 1024;
+/// `(command, help message key)` in `/help` order.
+const HELP: &[(&str, &str)] = &[
+    ("help", "help-help"),
+    ("curse", "help-curse"),
+    ("replace", "help-replace"),
+    ("clean", "help-clean"),
+    ("packs", "help-packs"),
+    ("language", "help-language"),
+    ("reloadcomments", "help-reloadcomments"),
+    ("addcomment", "help-addcomment"),
+    ("delcomment", "help-delcomment"),
+    ("listcomments", "help-listcomments"),
+    ("cookies", "help-cookies"),
+];
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
@@ -7,9 +52,36 @@ pub enum Command {
     /// Display this text.
     #[command(aliases = ["h", "?"])]
     Help,
-    /// Send a random comment
+    /// Send a random comment, aimed at a reply or user: /curse [generated] [@user|optout|optin]
+    #[command()]
+    Curse(String),
+    /// Delete link messages and repost the media with credit: /replace on|off
+    #[command()]
+    Replace(String),
+    /// Family-friendly messages and comments: /clean on|off
+    #[command()]
+    Clean(String),
+    /// Choose comment packs: /packs [name ...|all]
+    #[command()]
+    Packs(String),
+    /// Choose the bot language: /language [code|auto]
+    #[command()]
+    Language(String),
+    /// Reload comments and packs from disk (bot admins only)
+    #[command()]
+    ReloadComments,
+    /// Add a comment (bot admins only): /addcomment text
+    #[command()]
+    AddComment(String),
+    /// Delete an added comment (bot admins only): /delcomment id
     #[command()]
-    Curse,
+    DelComment(String),
+    /// List or search comments (bot admins only): /listcomments [search]
+    #[command()]
+    ListComments(String),
+    /// Replace a platform's cookie file (admin users only): /cookies platform [account]
+    #[command()]
+    Cookies(String),
 }
 
 /// Handle a command from the user.
@@ -18,16 +90,676 @@ pub enum Command {
 ///
 /// Returns a Teloxide error if the message fails to send.
 pub async fn answer(bot: &Bot, msg: &Message, cmd: Command) -> ResponseResult<()> {
+    let ctx = MessageContext::from_message(msg);
     match cmd {
-        Command::Help => {
-            bot.send_message(msg.chat.id, Command::descriptions().to_string())
-                .await?
+        Command::Help => ctx.send_message(bot, help_text(&ctx.lang)).await?,
+        Command::Curse(arg) => curse(bot, msg, ctx, arg.trim()).await?,
+        Command::Replace(arg) => {
+            let reply = set_switch(bot, msg, &ctx.lang, &REPLACE, arg.trim()).await?;
+            ctx.send_message(bot, reply).await?
+        }
+        Command::Clean(arg) => {
+            let reply = set_switch(bot, msg, &ctx.lang, &CLEAN, arg.trim()).await?;
+            ctx.send_message(bot, reply).await?
+        }
+        Command::Packs(arg) => {
+            let reply = set_packs(bot, msg, &ctx.lang, arg.trim()).await?;
+            ctx.send_message(bot, reply).await?
+        }
+        Command::Language(arg) => {
+            let reply = set_language(bot, msg, &ctx.lang, arg.trim()).await?;
+            ctx.send_message(bot, reply).await?
+        }
+        Command::ReloadComments => {
+            let reply = if is_bot_admin(msg.chat.id, msg.from.as_ref()) {
+                match reload_comments(&global_config().comments).await {
+                    Ok(count) => tr!(&ctx.lang, "reload-done", count = count),
+                    Err(e) => tr!(&ctx.lang, "reload-failed", error = e.to_string()),
+                }
+            } else {
+                tr!(&ctx.lang, "bot-admins-only")
+            };
+            ctx.send_message(bot, reply).await?
+        }
+        Command::AddComment(text) => {
+            let reply = add_comment(msg, &ctx.lang, text.trim()).await;
+            ctx.send_message(bot, reply).await?
         }
-        Command::Curse => {
-            let comment = global_comments().build_caption();
-            bot.send_message(msg.chat.id, comment).await?
+        Command::DelComment(id) => {
+            let reply = delete_comment(msg, &ctx.lang, id.trim()).await;
+            ctx.send_message(bot, reply).await?
+        }
+        Command::ListComments(query) => {
+            if !is_bot_admin(msg.chat.id, msg.from.as_ref()) {
+                ctx.send_message(bot, tr!(&ctx.lang, "bot-admins-only"))
+                    .await?;
+                return Ok(());
+            }
+            let (text, keyboard) = comment_page(&ctx.lang, query.trim(), 0);
+            let mut request = ctx.send_message(bot, text);
+            if let Some(keyboard) = keyboard {
+                request = request.reply_markup(keyboard);
+            }
+            request.await?
+        }
+        Command::Cookies(platform) => {
+            let reply = replace_cookies(bot, msg, &ctx.lang, platform.trim()).await?;
+            ctx.send_message(bot, reply).await?
         }
     };
 
     Ok(())
 }
+
+/// `/help` in `lang`.
+fn help_text(lang: &str) -> String {
+    HELP.iter()
+        .fold(tr!(lang, "help-header"), |text, (command, key)| {
+            format!("{text}\n/{command} — {}", tr!(lang, key))
+        })
+}
+
+/// `/curse [generated] [@user]`: a random (or generated) comment, aimed at
+/// the sender of the replied-to message or at a mentioned user unless they
+/// opted out or were cursed recently.
+async fn curse(
+    bot: &Bot,
+    msg: &Message,
+    mut ctx: MessageContext,
+    arg: &str,
+) -> ResponseResult<Message> {
+    let lang = ctx.lang.clone();
+    let (generated, arg) = match arg.split_once(char::is_whitespace) {
+        Some(("generated", rest)) => (true, rest.trim()),
+        _ if arg == "generated" => (true, ""),
+        _ => (false, arg),
+    };
+    if let opt @ ("optout" | "optin") = arg {
+        let reply = set_opted_out(msg, &lang, opt == "optout").await;
+        return ctx.send_message(bot, reply).await;
+    }
+
+    let target = if arg.is_empty() {
+        reply_target(msg).map(|(target, id)| {
+            ctx.reply_to = Some(id);
+            target
+        })
+    } else {
+        let Some(target) = text_mention(msg).or_else(|| Target::from_mention(arg)) else {
+            return ctx.send_message(bot, tr!(&lang, "curse-usage")).await;
+        };
+        Some(target)
+    };
+
+    if let Some(target) = &target {
+        let targets = global_targets();
+        let user = target.mention();
+        if targets.is_opted_out(target) {
+            let reply = tr!(&lang, "curse-target-opted-out", user = user);
+            return ctx.send_message(bot, reply).await;
+        }
+        let cooldown = global_config().curse_cooldown;
+        if let Err(left) = targets.try_curse(msg.chat.id, target, cooldown, Instant::now()) {
+            let minutes = left.as_secs().div_ceil(60).max(1);
+            let reply = tr!(&lang, "curse-cooldown", user = user, minutes = minutes);
+            return ctx.send_message(bot, reply).await;
+        }
+        ctx.caption.first_name = match target {
+            Target::User(user) => Some(user.first_name.clone()),
+            Target::Username(_) => None,
+        };
+        ctx.caption.user = Some(user);
+    }
+
+    let comments = global_comments();
+    let comment = if generated {
+        comments
+            .generate(&ctx.caption)
+            .unwrap_or_else(|| tr!(&lang, "curse-generate-failed"))
+    } else {
+        comments.build_caption(&ctx.caption)
+    };
+    let text = Caption::compose(&ctx.caption, comment, None).render_configured(MESSAGE_TEXT_LIMIT);
+    let mut request = ctx.send_message(bot, text);
+    if let Some(mode) = global_config().caption.format.parse_mode() {
+        request = request.parse_mode(mode);
+    }
+    request.await
+}
+
+async fn set_opted_out(msg: &Message, lang: &str, opted_out: bool) -> String {
+    let Some(user) = &msg.from else {
+        return tr!(lang, "curse-anonymous");
+    };
+    match global_targets().set_opted_out(user, opted_out).await {
+        Ok(()) if opted_out => tr!(lang, "curse-opted-out"),
+        Ok(()) => tr!(lang, "curse-opted-in"),
+        Err(e) => {
+            error!(%e, "failed to save curse opt-outs");
+            tr!(lang, "save-failed")
+        }
+    }
+}
+
+/// Sender of the message `msg` replies to. In forum topics every message
+/// "replies" to the topic's first message, which does not count.
+fn reply_target(msg: &Message) -> Option<(Target, MessageId)> {
+    let reply = msg.reply_to_message()?;
+    if msg.thread_id.is_some_and(|thread| thread.0 == reply.id) {
+        return None;
+    }
+    let user = reply.from.clone()?;
+    Some((Target::User(user), reply.id))
+}
+
+/// User mentioned by name (no username) in the command arguments.
+fn text_mention(msg: &Message) -> Option<Target> {
+    msg.entities()?
+        .iter()
+        .find_map(|entity| match &entity.kind {
+            MessageEntityKind::TextMention { user } => Some(Target::User(user.clone())),
+            _ => None,
+        })
+}
+
+/// A chat setting switched with `/<name> on|off`.
+struct Switch {
+    /// Command name, also the prefix of its messages (`<name>-status-on`, ...).
+    name: &'static str,
+    get: fn(&ChatSettings) -> bool,
+    set: fn(&mut ChatSettings, bool),
+}
+
+const REPLACE: Switch = Switch {
+    name: "replace",
+    get: |s| s.replace,
+    set: |s, on| s.replace = on,
+};
+
+const CLEAN: Switch = Switch {
+    name: "clean",
+    get: |s| s.clean,
+    set: |s, on| s.clean = on,
+};
+
+async fn set_switch(
+    bot: &Bot,
+    msg: &Message,
+    lang: &str,
+    switch: &Switch,
+    arg: &str,
+) -> ResponseResult<String> {
+    let name = switch.name;
+    let enable = match arg {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if (switch.get)(&global_settings().get(msg.chat.id)) {
+                "on"
+            } else {
+                "off"
+            };
+            return Ok(tr!(lang, &format!("{name}-status-{state}")));
+        }
+        _ => return Ok(tr!(lang, &format!("{name}-usage"))),
+    };
+
+    if !is_chat_admin(bot, msg).await? {
+        return Ok(tr!(lang, "chat-admins-only"));
+    }
+
+    if let Err(e) = global_settings()
+        .update(msg.chat.id, |s| (switch.set)(s, enable))
+        .await
+    {
+        error!(%e, "failed to save settings");
+        return Ok(tr!(lang, "save-failed"));
+    }
+
+    let change = if enable { "enabled" } else { "disabled" };
+    Ok(tr!(lang, &format!("{name}-{change}")))
+}
+
+async fn set_packs(bot: &Bot, msg: &Message, lang: &str, arg: &str) -> ResponseResult<String> {
+    let comments = global_comments();
+    let available = comments.pack_names().collect::<Vec<_>>();
+    if arg.is_empty() {
+        let selected = global_settings().get(msg.chat.id).packs;
+        let selected = if selected.is_empty() {
+            tr!(lang, "packs-all")
+        } else {
+            selected.join(", ")
+        };
+        return Ok(tr!(
+            lang,
+            "packs-status",
+            available = available.join(", "),
+            selected = selected,
+        ));
+    }
+
+    let packs = if arg == "all" {
+        Vec::new()
+    } else {
+        let names = arg
+            .split_whitespace()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        let unknown = names
+            .iter()
+            .filter(|name| !available.contains(&name.as_str()))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Ok(tr!(lang, "packs-unknown", packs = unknown.join(", ")));
+        }
+        names
+    };
+
+    if !is_chat_admin(bot, msg).await? {
+        return Ok(tr!(lang, "chat-admins-only"));
+    }
+
+    let reply = if packs.is_empty() {
+        tr!(lang, "packs-set-all")
+    } else {
+        tr!(lang, "packs-set", packs = packs.join(", "))
+    };
+    if let Err(e) = global_settings()
+        .update(msg.chat.id, |s| s.packs = packs)
+        .await
+    {
+        error!(%e, "failed to save settings");
+        return Ok(tr!(lang, "save-failed"));
+    }
+    Ok(reply)
+}
+
+async fn set_language(bot: &Bot, msg: &Message, lang: &str, arg: &str) -> ResponseResult<String> {
+    let catalog = global_catalog();
+    let available = catalog.languages().collect::<Vec<_>>().join(", ");
+    let language = match arg {
+        "" => {
+            let current = global_settings()
+                .get(msg.chat.id)
+                .language
+                .unwrap_or_else(|| tr!(lang, "language-auto"));
+            return Ok(tr!(
+                lang,
+                "language-status",
+                language = current,
+                available = available,
+            ));
+        }
+        "auto" => None,
+        code if catalog.supports(code) => Some(code.to_owned()),
+        code => {
+            return Ok(tr!(
+                lang,
+                "language-unknown",
+                language = code.to_owned(),
+                available = available,
+            ));
+        }
+    };
+
+    if !is_chat_admin(bot, msg).await? {
+        return Ok(tr!(lang, "chat-admins-only"));
+    }
+
+    let reply = language.as_ref().map_or_else(
+        || tr!(lang, "language-reset"),
+        |code| tr!(code, "language-set", language = code.clone()),
+    );
+    if let Err(e) = global_settings()
+        .update(msg.chat.id, |s| s.language = language)
+        .await
+    {
+        error!(%e, "failed to save settings");
+        return Ok(tr!(lang, "save-failed"));
+    }
+    Ok(reply)
+}
+
+/// Whether the sender may change chat settings (always true in private chats).
+async fn is_chat_admin(bot: &Bot, msg: &Message) -> ResponseResult<bool> {
+    if msg.chat.is_private() {
+        return Ok(true);
+    }
+    let Some(user) = &msg.from else {
+        // anonymous group admins post as the chat itself
+        return Ok(msg
+            .sender_chat
+            .as_ref()
+            .is_some_and(|c| c.id == msg.chat.id));
+    };
+    let member = bot.get_chat_member(msg.chat.id, user.id).await?;
+    Ok(member.is_privileged())
+}
+
+/// Whether `user` writing in `chat` may run bot-wide admin commands: listed
+/// in `ADMIN_USER_IDS` or writing from the admin chat.
+fn is_bot_admin(chat: ChatId, user: Option<&User>) -> bool {
+    let config = global_config();
+    config.chat_id == Some(chat) || user.is_some_and(|user| config.admin_users.contains(&user.id))
+}
+
+async fn add_comment(msg: &Message, lang: &str, text: &str) -> String {
+    if !is_bot_admin(msg.chat.id, msg.from.as_ref()) {
+        return tr!(lang, "bot-admins-only");
+    }
+    let Some(user) = &msg.from else {
+        return tr!(lang, "comment-anonymous");
+    };
+    if text.is_empty() {
+        return tr!(lang, "comment-add-usage");
+    }
+
+    let author = user.mention().unwrap_or_else(|| user.full_name());
+    match global_store().add(text, author, user.id).await {
+        Ok(id) => with_reload(lang, tr!(lang, "comment-added", id = id)).await,
+        Err(e) => tr!(lang, "comment-add-failed", error = e.to_string()),
+    }
+}
+
+async fn delete_comment(msg: &Message, lang: &str, id: &str) -> String {
+    if !is_bot_admin(msg.chat.id, msg.from.as_ref()) {
+        return tr!(lang, "bot-admins-only");
+    }
+    let Ok(id) = id.trim_start_matches('#').parse::<u32>() else {
+        return tr!(lang, "comment-delete-usage");
+    };
+
+    match global_store().remove(id).await {
+        Ok(Some(_)) => with_reload(lang, tr!(lang, "comment-deleted", id = id)).await,
+        Ok(None) => tr!(lang, "comment-not-found", id = id),
+        Err(e) => {
+            error!(%e, "failed to save comment store");
+            tr!(lang, "comment-save-failed")
+        }
+    }
+}
+
+/// Replace a cookie file with the `cookies.txt` document sent with the
+/// command (as its caption) or replied to. `arg` is the platform and
+/// optionally the account number in its pool.
+async fn replace_cookies(
+    bot: &Bot,
+    msg: &Message,
+    lang: &str,
+    arg: &str,
+) -> ResponseResult<String> {
+    let upload = Some(msg)
+        .filter(|m| m.document().is_some())
+        .or_else(|| msg.reply_to_message().filter(|m| m.document().is_some()));
+    // the upload holds session secrets, do not leave it in the chat whatever
+    // happens next; its file stays downloadable
+    if let Some(upload) = upload
+        && let Err(e) = bot.delete_message(upload.chat.id, upload.id).await
+    {
+        warn!("cannot delete cookie file message: {e}");
+    }
+    // the admin chat is not enough: cookies carry account sessions
+    let admins = &global_config().admin_users;
+    if !msg
+        .from
+        .as_ref()
+        .is_some_and(|user| admins.contains(&user.id))
+    {
+        return Ok(tr!(lang, "cookies-admins-only"));
+    }
+    let mut words = arg.split_whitespace();
+    let platform = words.next().and_then(Platform::parse);
+    let account = words
+        .next()
+        .map_or(Some(1), |n| n.trim_start_matches('#').parse::<usize>().ok());
+    let (Some(platform), Some(account), None, Some(upload)) =
+        (platform, account, words.next(), upload)
+    else {
+        let platforms = Platform::ALL.map(Platform::name).join(", ");
+        return Ok(tr!(lang, "cookies-usage", platforms = platforms));
+    };
+    let paths = platform.cookies_paths(global_config());
+    if paths.is_empty() {
+        return Ok(tr!(
+            lang,
+            "cookies-not-configured",
+            platform = platform.name(),
+            variable = platform.env_var()
+        ));
+    }
+    let Some(path) = account.checked_sub(1).and_then(|i| paths.get(i)) else {
+        return Ok(tr!(
+            lang,
+            "cookies-unknown-account",
+            platform = platform.name(),
+            count = paths.len()
+        ));
+    };
+    let Some(document) = upload.document() else {
+        return Ok(tr!(lang, "cookies-download-failed"));
+    };
+    if document.file.size > COOKIE_FILE_LIMIT {
+        return Ok(tr!(lang, "cookies-too-large"));
+    }
+
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut content = Vec::new();
+    if let Err(e) = bot.download_file(&file.path, &mut content).await {
+        error!(%e, "failed to download cookie file");
+        return Ok(tr!(lang, "cookies-download-failed"));
+    }
+
+    let text = String::from_utf8_lossy(&content);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let report = match cookies::replace(platform, path, &text, now).await {
+        Ok(report) => report,
+        Err(e) => return Ok(tr!(lang, "cookies-invalid", error = e.to_string())),
+    };
+    info!(platform = platform.name(), path = %path.display(), "replaced cookie file");
+    global_monitor().reset(platform, path);
+
+    let sessions = report
+        .sessions
+        .iter()
+        .map(|(name, expiry)| match *expiry {
+            Expiry::Session => tr!(lang, "cookies-expiry-session", name = name.as_str()),
+            Expiry::At(at) => tr!(
+                lang,
+                "cookies-expiry-at",
+                name = name.as_str(),
+                date = format_date(at)
+            ),
+            Expiry::Expired(at) => tr!(
+                lang,
+                "cookies-expiry-expired",
+                name = name.as_str(),
+                date = format_date(at)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(tr!(
+        lang,
+        "cookies-replaced",
+        platform = platform.name(),
+        count = report.cookies,
+        foreign = report.foreign,
+        sessions = sessions
+    ))
+}
+
+/// Reload comments after a store change so it takes effect right away.
+async fn with_reload(lang: &str, reply: String) -> String {
+    match reload_comments(&global_config().comments).await {
+        Ok(_) => reply,
+        Err(e) => tr!(
+            lang,
+            "comment-reload-pending",
+            reply = reply,
+            error = e.to_string(),
+        ),
+    }
+}
+
+/// One `/listcomments` page for `query` with its navigation buttons.
+fn comment_page(lang: &str, query: &str, page: usize) -> (String, Option<InlineKeyboardMarkup>) {
+    let needle = query.to_lowercase();
+    let matches = |text: &str| needle.is_empty() || text.to_lowercase().contains(&needle);
+
+    let comments = global_comments();
+    let mut lines = comments
+        .entries()
+        .filter(|(_, c)| c.id.is_none() && matches(c.template.source()))
+        .map(|(pack, c)| format!("[{pack}] {}", shorten(c.template.source())))
+        .collect::<Vec<_>>();
+    lines.extend(
+        global_store()
+            .list()
+            .into_iter()
+            .filter(|c| matches(&c.text))
+            .map(|c| {
+                let date = c.added_on();
+                let by = tr!(lang, "list-by", author = c.added_by, date = date);
+                format!("#{} {} {by}", c.id, shorten(&c.text))
+            }),
+    );
+
+    if lines.is_empty() {
+        return (tr!(lang, "list-empty"), None);
+    }
+
+    let pages = lines.len().div_ceil(PAGE_SIZE);
+    let page = page.min(pages - 1);
+    let header = if query.is_empty() {
+        tr!(
+            lang,
+            "list-header",
+            count = lines.len(),
+            page = page + 1,
+            pages = pages,
+        )
+    } else {
+        tr!(
+            lang,
+            "list-header-search",
+            count = lines.len(),
+            query = query.to_owned(),
+            page = page + 1,
+            pages = pages,
+        )
+    };
+    let body = lines
+        .iter()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut buttons = Vec::new();
+    if page > 0 {
+        buttons.push(InlineKeyboardButton::callback(
+            tr!(lang, "list-prev"),
+            list_callback_data(page - 1, query),
+        ));
+    }
+    if page + 1 < pages {
+        buttons.push(InlineKeyboardButton::callback(
+            tr!(lang, "list-next"),
+            list_callback_data(page + 1, query),
+        ));
+    }
+    let keyboard = (!buttons.is_empty()).then(|| InlineKeyboardMarkup::new([buttons]));
+    (format!("{header}\n\n{body}"), keyboard)
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= LIST_TEXT_LIMIT {
+        return text.to_owned();
+    }
+    let short = text
+        .chars()
+        .take(LIST_TEXT_LIMIT.saturating_sub(1))
+        .collect::<String>();
+    format!("{short}…")
+}
+
+/// `lc:<page>:<query>`, with the query cut to fit Telegram's 64 byte limit.
+fn list_callback_data(page: usize, query: &str) -> String {
+    let mut data = format!("{LIST_CALLBACK_PREFIX}{page}:");
+    for c in query.chars() {
+        if data.len() + c.len_utf8() > CALLBACK_DATA_LIMIT {
+            break;
+        }
+        data.push(c);
+    }
+    data
+}
+
+fn parse_list_callback(data: &str) -> Option<(usize, &str)> {
+    let (page, query) = data.strip_prefix(LIST_CALLBACK_PREFIX)?.split_once(':')?;
+    Some((page.parse().ok()?, query))
+}
+
+/// Handle a press on a `/listcomments` page button.
+///
+/// # Errors
+///
+/// Returns a Teloxide error if editing the listing or answering the query fails.
+pub async fn answer_callback(bot: &Bot, query: &CallbackQuery) -> ResponseResult<()> {
+    let (Some((page, search)), Some(msg)) = (
+        query.data.as_deref().and_then(parse_list_callback),
+        query.regular_message(),
+    ) else {
+        bot.answer_callback_query(query.id.clone()).await?;
+        return Ok(());
+    };
+
+    let lang = global_catalog().resolve(
+        global_settings().get(msg.chat.id).language.as_deref(),
+        query.from.language_code.as_deref(),
+    );
+    if !is_bot_admin(msg.chat.id, Some(&query.from)) {
+        bot.answer_callback_query(query.id.clone())
+            .text(tr!(&lang, "bot-admins-only"))
+            .await?;
+        return Ok(());
+    }
+
+    let (text, keyboard) = comment_page(&lang, search, page);
+    let mut request = bot.edit_message_text(msg.chat.id, msg.id, text);
+    if let Some(keyboard) = keyboard {
+        request = request.reply_markup(keyboard);
+    }
+    request.await?;
+    bot.answer_callback_query(query.id.clone()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_callback_round_trip() {
+        let data = list_callback_data(3, "box box");
+        assert_eq!(parse_list_callback(&data), Some((3, "box box")));
+
+        let long = list_callback_data(12, &"é".repeat(100));
+        assert!(long.len() <= CALLBACK_DATA_LIMIT);
+        assert!(parse_list_callback(&long).is_some());
+        assert_eq!(parse_list_callback("other:1:x"), None);
+    }
+
+    #[test]
+    fn help_lists_every_command() {
+        let help = help_text("en");
+        for (command, _) in HELP {
+            assert!(help.contains(&format!("/{command} ")), "{command}");
+        }
+        assert_eq!(HELP.len(), Command::bot_commands().len());
+    }
+}
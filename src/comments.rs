@@ -1,16 +1,31 @@
-use crate::error::{Error, Result};
-use rand::{rng, seq::IndexedRandom};
+use crate::{
+    caption::{MEDIA_CAPTION_LIMIT, utf16_len},
+    config::{CommentSelection, CommentsConfig, GeneratorMode},
+    error::{Error, Result},
+    history::global_history,
+    markov::Markov,
+    packs::{Comment, Pack, TimeOfDay},
+    template::{CaptionContext, Template},
+};
+use rand::{Rng, rng, seq::IndexedRandom};
 use std::{
     fmt::Display,
+    io::ErrorKind,
     path::Path,
-    sync::{Arc, OnceLock},
+    sync::{Arc, OnceLock, PoisonError, RwLock},
 };
-use tokio::fs::read_to_string;
+use tokio::fs::{read_dir, read_to_string};
 
-static GLOBAL_COMMENTS: OnceLock<Comments> = OnceLock::new();
+static GLOBAL_COMMENTS: OnceLock<RwLock<Arc<Comments>>> = OnceLock::new();
 
 const DISCLAIMER: &str = "(Roleplay — fictional messages for entertainment.)";
-pub const TELEGRAM_CAPTION_LIMIT: usize = 4096;
+/// Name of the pack built from the plaintext comments file.
+pub const DEFAULT_PACK: &str = "default";
+/// Words of context for generated comments.
+const MARKOV_ORDER: usize = 2;
+/// Chain walks per generated comment before giving up.
+const GENERATE_ATTEMPTS: usize = 20;
+const MAX_GENERATED_WORDS: usize = 60;
 const FALLBACK_COMMENTS: &[&str] = &[
     "Oh come on, that's brilliant — and slightly chaotic, like always.",
     "That is a proper bit of craftsmanship — then someone presses the red button.",
@@ -21,79 +36,301 @@ const FALLBACK_COMMENTS: &[&str] = &[
 #[derive(Debug)]
 pub struct Comments {
     pub disclaimer: String,
-    lines: Arc<Vec<String>>,
+    packs: Arc<Vec<Pack>>,
+    /// Offset from UTC used to resolve time-of-day tags.
+    utc_offset_hours: i32,
+    selection: CommentSelection,
+    generator: GeneratorMode,
+    /// Markov chains per pack language (`None` for language-neutral packs).
+    chains: Vec<(Option<String>, Markov)>,
+    /// Word stems checked on generated comments in clean mode.
+    profanity: Vec<String>,
+    /// Comments and packs left out of the last load, with the reason.
+    problems: Vec<String>,
 }
 
 impl Comments {
     /// Create a small dummy/default Comments instance (useful for tests or fallback).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a built-in fallback comment is not a valid template.
     #[must_use]
     pub fn dummy() -> Self {
         let lines = FALLBACK_COMMENTS
             .iter()
-            .map(ToString::to_string)
+            .map(|line| Template::parse(line).expect("fallback comments are valid templates"))
             .collect::<Vec<_>>();
+        Self::from_packs(vec![plain_pack(lines)])
+    }
+
+    fn from_packs(packs: Vec<Pack>) -> Self {
         Self {
             disclaimer: DISCLAIMER.into(),
-            lines: lines.into(),
+            packs: packs.into(),
+            utc_offset_hours: 0,
+            selection: CommentSelection::Random,
+            generator: GeneratorMode::Off,
+            chains: Vec::new(),
+            profanity: Vec::new(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Load the plaintext comments file and every `*.toml` pack in the packs
+    /// directory, adding `custom` comments (from the comment store) to the
+    /// `default` pack. Missing sources are skipped.
+    ///
+    /// Unreadable or malformed packs, packs reusing a name and invalid
+    /// comments are left out and listed in [`Comments::problems`], as is an
+    /// unreadable or empty comments file.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the packs directory cannot be listed.
+    /// - Returns `Error::Other` if no usable comments were found.
+    pub async fn load(config: &CommentsConfig, custom: Vec<Comment>) -> Result<Self> {
+        let mut problems = Vec::new();
+        let mut default = match load_plain_pack(&config.file).await {
+            // an emptied file would silently remove the default pack
+            Ok(pack) if pack.comments.is_empty() && pack.skipped.is_empty() => {
+                problems.push(format!("{}: no comments", config.file.display()));
+                pack
+            }
+            Ok(pack) => pack,
+            Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => plain_pack(Vec::new()),
+            Err(e) => {
+                problems.push(format!("{}: {e}", config.file.display()));
+                plain_pack(Vec::new())
+            }
+        };
+        default.comments.extend(custom);
+        let mut packs = vec![default];
+
+        let mut paths = Vec::new();
+        match read_dir(&config.packs_dir).await {
+            Ok(mut entries) => {
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "toml") {
+                        paths.push(path);
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        paths.sort();
+
+        for path in paths {
+            let pack = match Pack::load(&path).await {
+                Ok(pack) => pack,
+                Err(e) => {
+                    problems.push(format!("{}: {e}", path.display()));
+                    continue;
+                }
+            };
+            if packs.iter().any(|p: &Pack| p.name == pack.name) {
+                problems.push(format!(
+                    "{}: duplicate comment pack `{}`",
+                    path.display(),
+                    pack.name
+                ));
+                continue;
+            }
+            packs.push(pack);
+        }
+        problems.extend(packs.iter().flat_map(|pack| {
+            let source = if pack.name == DEFAULT_PACK {
+                config.file.display().to_string()
+            } else {
+                format!("pack `{}`", pack.name)
+            };
+            pack.skipped
+                .iter()
+                .map(move |skipped| format!("{source}: {skipped}"))
+        }));
+
+        if packs.iter().all(|p| p.comments.is_empty()) {
+            let mut message = "no usable comments found".to_owned();
+            if !problems.is_empty() {
+                message = format!("{message} ({})", problems.join("; "));
+            }
+            return Err(Error::other(message));
+        }
+        for comment in packs.iter_mut().flat_map(|p| &mut p.comments) {
+            comment.profane |= is_profane(comment.template.source(), &config.profanity);
         }
+
+        let chains = if config.generator == GeneratorMode::Off {
+            Vec::new()
+        } else {
+            train_chains(&packs)
+        };
+        Ok(Self {
+            utc_offset_hours: config.utc_offset_hours,
+            selection: config.selection,
+            generator: config.generator,
+            chains,
+            profanity: config.profanity.clone(),
+            problems,
+            ..Self::from_packs(packs)
+        })
     }
 
     /// Load comments from a plaintext file asynchronously.
     ///
+    /// Every line is a [`Template`]; invalid ones are reported with their line number.
+    ///
     /// # Errors
     ///
     /// - Returns `Error::Io` if reading the file fails.
+    /// - Returns `Error::ValidationFailed` listing every invalid template.
     /// - Returns `Error::Other` if the file contains no usable lines.
     pub async fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = read_to_string(path).await?;
-
-        let lines = content
-            .lines()
-            .map(str::trim)
-            .filter(|l| !l.is_empty() && !l.starts_with('#'))
-            .map(ToString::to_string)
-            .collect::<Vec<_>>();
-
-        if lines.is_empty() {
+        let pack = load_plain_pack(path.as_ref()).await?;
+        if !pack.skipped.is_empty() {
+            return Err(Error::validation_falied(format!(
+                "invalid comment templates: {}",
+                pack.skipped.join("; ")
+            )));
+        }
+        if pack.comments.is_empty() {
             return Err(Error::other("comments file contains no usable lines"));
         }
+        Ok(Self::from_packs(vec![pack]))
+    }
 
-        Ok(Self {
-            disclaimer: DISCLAIMER.into(),
-            lines: lines.into(),
-        })
+    /// Comments and packs that were left out of the load, with the reason.
+    #[must_use]
+    pub fn problems(&self) -> &[String] {
+        &self.problems
     }
 
-    /// Pick a random comment. Falls back to a default if the list is empty.
+    /// Number of comments across all packs.
     #[must_use]
-    pub fn pick(&self) -> &str {
-        let mut rng = rng();
-        self.lines
-            .choose(&mut rng)
-            .map_or(FALLBACK_COMMENTS[0], AsRef::as_ref)
+    pub fn len(&self) -> usize {
+        self.packs.iter().map(|p| p.comments.len()).sum()
     }
 
-    /// Build a caption by picking a random comment and truncating if necessary.
     #[must_use]
-    pub fn build_caption(&self) -> String {
-        let mut caption = self.pick().to_string();
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-        // Trancate if too long for Telegram
-        if caption.chars().count() > TELEGRAM_CAPTION_LIMIT {
-            let truncated = caption
-                .chars()
-                .take(TELEGRAM_CAPTION_LIMIT.saturating_sub(3))
-                .collect::<String>();
-            caption = format!("{truncated}...");
+    /// Every comment with the name of its pack.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Comment)> {
+        self.packs
+            .iter()
+            .flat_map(|p| p.comments.iter().map(|c| (p.name.as_str(), c)))
+    }
+
+    /// Names of all loaded packs.
+    pub fn pack_names(&self) -> impl Iterator<Item = &str> {
+        self.packs.iter().map(|p| p.name.as_str())
+    }
+
+    /// Comments from the chat's packs whose tags fit `ctx` at `time`.
+    ///
+    /// Packs in the chat's language replace language-neutral packs; packs in
+    /// other languages are never used. Profane comments are skipped in clean
+    /// mode.
+    fn candidates<'a>(
+        &'a self,
+        ctx: &'a CaptionContext,
+        time: TimeOfDay,
+    ) -> impl Iterator<Item = &'a Comment> {
+        let chosen = self
+            .packs
+            .iter()
+            .filter(|p| ctx.packs.is_empty() || ctx.packs.contains(&p.name))
+            .collect::<Vec<_>>();
+        let localized = ctx
+            .lang
+            .as_ref()
+            .filter(|lang| chosen.iter().any(|p| p.language.as_ref() == Some(*lang)));
+        chosen
+            .into_iter()
+            .filter(move |p| p.language.as_ref() == localized)
+            .flat_map(|p| &p.comments)
+            .filter(move |c| c.weight > 0.0 && c.tags.matches(ctx, time))
+            .filter(move |c| !(ctx.clean && c.profane))
+    }
+
+    /// Pick a comment for the chat and render it with `ctx`.
+    ///
+    /// Only comments from the chat's packs whose tags match are considered,
+    /// and lines whose placeholders can all be filled are preferred. Unless
+    /// selection is random, comments recently shown in the chat are skipped
+    /// (see [`CommentSelection`]). Falls back to a default if nothing matches.
+    #[must_use]
+    pub fn pick(&self, ctx: &CaptionContext) -> String {
+        let time = TimeOfDay::now(self.utc_offset_hours);
+        let candidates = self.candidates(ctx, time).collect::<Vec<_>>();
+        let satisfied = candidates
+            .iter()
+            .copied()
+            .filter(|c| c.template.is_satisfied_by(ctx))
+            .collect::<Vec<_>>();
+        let pool = if satisfied.is_empty() {
+            &candidates
+        } else {
+            &satisfied
+        };
+
+        let chat = ctx
+            .chat_id
+            .filter(|_| self.selection != CommentSelection::Random);
+        let served = chat.map(|chat| global_history().served(chat));
+        let Some((comment, reset)) =
+            select(pool, served.as_deref().unwrap_or_default(), self.selection)
+        else {
+            return FALLBACK_COMMENTS[0].to_owned();
+        };
+        if let Some(chat) = chat {
+            let keep = match self.selection {
+                CommentSelection::NoRepeat(n) => n,
+                CommentSelection::Random | CommentSelection::Shuffle => usize::MAX,
+            };
+            global_history().record(chat, comment.template.source(), reset, keep);
         }
-        caption
+        comment.template.render(ctx)
     }
 
-    /// Get a reference to the underlying lines for debugging or testing.
-    #[cfg(test)]
+    /// Generate a new comment from the chain for the chat's language (or
+    /// the language-neutral one) and render it with `ctx`.
+    ///
+    /// Returns `None` when generation is off or every attempt was a verbatim
+    /// copy, too long, not a valid template or profane in clean mode.
     #[must_use]
-    pub fn lines(&self) -> &[String] {
-        &self.lines
+    pub fn generate(&self, ctx: &CaptionContext) -> Option<String> {
+        self.generate_with(ctx, &mut rng())
+    }
+
+    fn generate_with<R: Rng + ?Sized>(&self, ctx: &CaptionContext, rng: &mut R) -> Option<String> {
+        let lang = ctx
+            .lang
+            .as_ref()
+            .filter(|lang| self.chains.iter().any(|(l, _)| l.as_ref() == Some(*lang)));
+        let (_, chain) = self.chains.iter().find(|(l, _)| l.as_ref() == lang)?;
+        (0..GENERATE_ATTEMPTS)
+            .filter_map(|_| chain.generate(rng, MAX_GENERATED_WORDS))
+            .filter(|text| !(ctx.clean && is_profane(text, &self.profanity)))
+            .filter_map(|text| Template::parse(&text).ok())
+            .map(|template| template.render(ctx))
+            .find(|text| utf16_len(text) <= MEDIA_CAPTION_LIMIT)
+    }
+
+    /// Pick (or generate, see [`GeneratorMode`]) a comment for `ctx`.
+    ///
+    /// The result is not truncated; see [`crate::caption::Caption`].
+    #[must_use]
+    pub fn build_caption(&self, ctx: &CaptionContext) -> String {
+        let generated = match self.generator {
+            GeneratorMode::Captions => self.generate(ctx),
+            GeneratorMode::Off | GeneratorMode::OnDemand => None,
+        };
+        generated.unwrap_or_else(|| self.pick(ctx))
     }
 
     /// Initialize the global comments (call once at startup).
@@ -103,9 +340,122 @@ impl Comments {
     /// Returns `Error::Other` when the global is already initialized.
     pub fn init(self) -> Result<()> {
         GLOBAL_COMMENTS
-            .set(self)
+            .set(RwLock::new(Arc::new(self)))
             .map_err(|_| Error::other("comments already initialized"))
     }
+
+    /// Atomically replace the global comments. Captions being built keep
+    /// the set they started with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if comments have not been initialized.
+    pub fn swap(self) {
+        let global = GLOBAL_COMMENTS.get().expect("comments not initialized");
+        *global.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(self);
+    }
+}
+
+/// Choose from `pool`, skipping comments in `served` (oldest first).
+///
+/// Returns the comment and whether the shuffle bag was refilled, i.e. the
+/// served list should start over.
+fn select<'a>(
+    pool: &[&'a Comment],
+    served: &[String],
+    selection: CommentSelection,
+) -> Option<(&'a Comment, bool)> {
+    let unserved = |skip: &[String]| {
+        pool.iter()
+            .copied()
+            .filter(|c| !skip.iter().any(|s| s == c.template.source()))
+            .collect::<Vec<_>>()
+    };
+    let mut rng = rng();
+    match selection {
+        CommentSelection::Random => pool
+            .choose_weighted(&mut rng, |c| c.weight)
+            .ok()
+            .map(|c| (*c, false)),
+        CommentSelection::Shuffle => {
+            let fresh = unserved(served);
+            if let Some(c) = fresh.choose(&mut rng) {
+                return Some((c, false));
+            }
+            // bag is empty: refill, but do not repeat the last comment right away
+            let refill = unserved(&served[served.len().saturating_sub(1)..]);
+            let refill = if refill.is_empty() { pool } else { &refill };
+            refill.choose(&mut rng).map(|c| (*c, true))
+        }
+        CommentSelection::NoRepeat(n) => {
+            let fresh = unserved(&served[served.len().saturating_sub(n)..]);
+            let pool = if fresh.is_empty() { pool } else { &fresh };
+            pool.choose_weighted(&mut rng, |c| c.weight)
+                .ok()
+                .map(|c| (*c, false))
+        }
+    }
+}
+
+/// One chain per pack language, trained on every comment that can be picked.
+fn train_chains(packs: &[Pack]) -> Vec<(Option<String>, Markov)> {
+    let mut languages = packs.iter().map(|p| p.language.clone()).collect::<Vec<_>>();
+    languages.sort();
+    languages.dedup();
+    languages
+        .into_iter()
+        .map(|lang| {
+            let lines = packs
+                .iter()
+                .filter(|p| p.language == lang)
+                .flat_map(|p| &p.comments)
+                .filter(|c| c.weight > 0.0)
+                .map(|c| c.template.source());
+            let chain = Markov::new(MARKOV_ORDER, lines);
+            (lang, chain)
+        })
+        .filter(|(_, chain)| !chain.is_empty())
+        .collect()
+}
+
+/// Whether a word in `text` starts with one of the lowercase `stems`.
+fn is_profane(text: &str, stems: &[String]) -> bool {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .any(|word| stems.iter().any(|stem| word.starts_with(stem.as_str())))
+}
+
+fn plain_pack(lines: Vec<Template>) -> Pack {
+    Pack {
+        name: DEFAULT_PACK.into(),
+        language: None,
+        comments: lines.into_iter().map(Comment::plain).collect(),
+        skipped: Vec::new(),
+    }
+}
+
+/// Parse a plaintext comments file into the `default` pack, skipping
+/// invalid templates.
+async fn load_plain_pack(path: &Path) -> Result<Pack> {
+    let content = read_to_string(path).await?;
+
+    let mut lines = Vec::new();
+    let mut skipped = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Template::parse(line) {
+            Ok(template) => lines.push(template),
+            Err(e) => skipped.push(format!("line {}: {e}", n + 1)),
+        }
+    }
+
+    Ok(Pack {
+        skipped,
+        ..plain_pack(lines)
+    })
 }
 
 /// Get global comments (initialized by `Comments::init(self)`).
@@ -115,13 +465,14 @@ impl Comments {
 /// Panics if comments have not been initialized.
 #[inline]
 #[must_use]
-pub fn global_comments() -> &'static Comments {
-    GLOBAL_COMMENTS.get().expect("comments not initialized")
+pub fn global_comments() -> Arc<Comments> {
+    let global = GLOBAL_COMMENTS.get().expect("comments not initialized");
+    Arc::clone(&global.read().unwrap_or_else(PoisonError::into_inner))
 }
 
 impl Display for Comments {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.build_caption())
+        write!(f, "{}", self.build_caption(&CaptionContext::default()))
     }
 }
 
@@ -140,32 +491,326 @@ impl From<&Comments> for String {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn single_pack(lines: &[&str]) -> Comments {
+        let lines = lines
+            .iter()
+            .map(|line| Template::parse(line).expect("valid"))
+            .collect();
+        Comments::from_packs(vec![plain_pack(lines)])
+    }
+
     #[test]
     fn dummy_comments() {
         let comments = Comments::dummy();
-        assert_eq!(comments.lines.len(), FALLBACK_COMMENTS.len());
-        assert!(!comments.lines.is_empty());
+        assert_eq!(comments.packs[0].comments.len(), FALLBACK_COMMENTS.len());
+        assert_eq!(comments.pack_names().collect::<Vec<_>>(), [DEFAULT_PACK]);
     }
 
     #[test]
-    fn build_caption_truncation() {
-        let long_comment = "A".repeat(TELEGRAM_CAPTION_LIMIT + 10);
-        let comments = Comments {
-            disclaimer: DISCLAIMER.into(),
-            lines: Arc::new(vec![long_comment]),
+    fn pick_fallbakc() {
+        let empty_comment = single_pack(&[]);
+        assert_eq!(
+            empty_comment.pick(&CaptionContext::default()),
+            FALLBACK_COMMENTS[0]
+        );
+    }
+
+    #[test]
+    fn pick_prefers_satisfied_templates() {
+        let comments = single_pack(&["Uploaded by {uploader}", "Nice one, {user|mate}"]);
+        let ctx = CaptionContext::default();
+        for _ in 0..20 {
+            assert_eq!(comments.pick(&ctx), "Nice one, mate");
+        }
+    }
+
+    #[test]
+    fn pick_respects_weights_and_chat_packs() {
+        let racing = Pack::from_toml(
+            "[[comments]]\ntext = \"never\"\nweight = 0\n\n[[comments]]\ntext = \"box box\"\n",
+            "racing",
+        )
+        .expect("valid pack");
+        let comments = Comments::from_packs(vec![
+            plain_pack(vec![Template::parse("plain").expect("valid")]),
+            racing,
+        ]);
+        let ctx = CaptionContext {
+            packs: vec!["racing".into()],
+            ..CaptionContext::default()
         };
+        for _ in 0..20 {
+            assert_eq!(comments.pick(&ctx), "box box");
+        }
+    }
 
-        let caption = comments.build_caption();
-        assert_eq!(caption.chars().count(), TELEGRAM_CAPTION_LIMIT);
-        assert!(caption.ends_with("..."));
+    fn plain(lines: &[&str]) -> Vec<Comment> {
+        lines
+            .iter()
+            .map(|line| Comment::plain(Template::parse(line).expect("valid")))
+            .collect()
     }
 
     #[test]
-    fn pick_fallbakc() {
-        let empty_comment = Comments {
-            disclaimer: DISCLAIMER.into(),
-            lines: Arc::new(Vec::new()),
+    fn shuffle_serves_everything_before_repeating() {
+        let comments = plain(&["a", "b", "c", "d"]);
+        let pool = comments.iter().collect::<Vec<_>>();
+        let mut served = Vec::<String>::new();
+        for round in 0..3 {
+            for _ in 0..pool.len() {
+                let (c, reset) =
+                    select(&pool, &served, CommentSelection::Shuffle).expect("non-empty pool");
+                let last = served.last().cloned();
+                assert_eq!(reset, served.len() == pool.len(), "round {round}");
+                if reset {
+                    served.clear();
+                }
+                assert_ne!(Some(c.template.source()), last.as_deref());
+                served.push(c.template.source().to_owned());
+            }
+            let mut seen = served.clone();
+            seen.sort();
+            assert_eq!(seen, ["a", "b", "c", "d"]);
+        }
+    }
+
+    #[test]
+    fn no_repeat_skips_recent() {
+        let comments = plain(&["a", "b", "c"]);
+        let pool = comments.iter().collect::<Vec<_>>();
+        let served = ["a".to_owned(), "b".to_owned()];
+        for _ in 0..20 {
+            let (c, _) = select(&pool, &served, CommentSelection::NoRepeat(2)).expect("pick");
+            assert_eq!(c.template.source(), "c");
+        }
+    }
+
+    #[test]
+    fn pick_prefers_packs_in_chat_language() {
+        let latvian = Pack::from_toml("language = \"lv\"\n[[comments]]\ntext = \"Labs\"\n", "lv")
+            .expect("valid pack");
+        let comments = Comments::from_packs(vec![
+            plain_pack(vec![Template::parse("Nice").expect("valid")]),
+            latvian,
+        ]);
+        let ctx = |lang: &str| CaptionContext {
+            lang: Some(lang.into()),
+            ..CaptionContext::default()
+        };
+        assert_eq!(comments.pick(&ctx("lv")), "Labs");
+        assert_eq!(comments.pick(&ctx("en")), "Nice");
+        assert_eq!(comments.pick(&CaptionContext::default()), "Nice");
+    }
+
+    #[test]
+    fn profanity_matches_word_stems() {
+        let stems = ["fok".to_owned()];
+        assert!(is_profane("P-foking-18. Again.", &stems));
+        assert!(is_profane("FOK!", &stems));
+        assert!(!is_profane("Folks, again", &stems));
+    }
+
+    #[tokio::test]
+    async fn clean_mode_skips_profane_comments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("comments.txt");
+        let packs_dir = dir.path().join("packs");
+        tokio::fs::write(&file, "Lovely\nPure foking magic\n")
+            .await
+            .expect("write comments");
+        tokio::fs::create_dir(&packs_dir).await.expect("mkdir");
+        tokio::fs::write(
+            packs_dir.join("tagged.toml"),
+            "[[comments]]\ntext = \"Bloody hell\"\nprofane = true\n",
+        )
+        .await
+        .expect("write pack");
+
+        let config = CommentsConfig {
+            file,
+            packs_dir,
+            profanity: vec!["fok".into()],
+            ..CommentsConfig::default()
+        };
+        let comments = Comments::load(&config, Vec::new()).await.expect("load");
+        let ctx = CaptionContext {
+            clean: true,
+            ..CaptionContext::default()
+        };
+        for _ in 0..20 {
+            assert_eq!(comments.pick(&ctx), "Lovely");
+        }
+    }
+
+    #[test]
+    fn generate_is_seeded_and_renders_placeholders() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let lines = [
+            "{user} drives like the car is on fire",
+            "{user} brakes like the car is a boat",
+            "The car is on fire again, {user|mate}",
+        ];
+        let mut comments = single_pack(&lines);
+        comments.chains = train_chains(&comments.packs);
+        let ctx = CaptionContext {
+            user: Some("@kris".into()),
+            ..CaptionContext::default()
+        };
+
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10)
+                .filter_map(|_| comments.generate_with(&ctx, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        let generated = run(42);
+        assert!(!generated.is_empty());
+        assert_eq!(generated, run(42));
+        for text in &generated {
+            assert!(!text.contains('{'), "{text}");
+            assert!(
+                lines
+                    .iter()
+                    .all(|line| Template::parse(line).expect("valid").render(&ctx) != *text)
+            );
+        }
+    }
+
+    #[test]
+    fn generate_stays_within_caption_limit() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let long = (0..30)
+            .map(|i| format!("{i:0>40}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // "other here go" + the long tail is new and too long, "start here
+        // go now" is new and fits
+        let lines = [
+            format!("start here go {long} end"),
+            "other here go now".to_owned(),
+        ];
+        let mut comments = single_pack(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+        comments.chains = train_chains(&comments.packs);
+        let ctx = CaptionContext::default();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let generated = (0..50)
+            .filter_map(|_| comments.generate_with(&ctx, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(!generated.is_empty());
+        assert!(generated.iter().all(|text| text == "start here go now"));
+    }
+
+    #[tokio::test]
+    async fn load_reports_invalid_line_numbers() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("comments.txt");
+        tokio::fs::write(&path, "# header\nfine {user}\n\nbad {nope}\n")
+            .await
+            .expect("write comments");
+
+        let err = Comments::load_from_file(&path).await.expect_err("invalid");
+        assert!(err.to_string().contains("line 4"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn load_merges_packs_dir() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let packs_dir = dir.path().join("packs");
+        tokio::fs::create_dir(&packs_dir).await.expect("mkdir");
+        tokio::fs::write(
+            packs_dir.join("racing.toml"),
+            "[[comments]]\ntext = \"box\"\n",
+        )
+        .await
+        .expect("write pack");
+
+        let config = CommentsConfig {
+            file: dir.path().join("missing.txt"),
+            packs_dir,
+            ..CommentsConfig::default()
+        };
+        let custom = Comment::plain(Template::parse("custom").expect("valid"));
+        let comments = Comments::load(&config, vec![custom])
+            .await
+            .expect("packs only");
+        assert_eq!(
+            comments.pack_names().collect::<Vec<_>>(),
+            [DEFAULT_PACK, "racing"]
+        );
+        assert_eq!(comments.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn load_reports_empty_comments_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let packs_dir = dir.path().join("packs");
+        tokio::fs::create_dir(&packs_dir).await.expect("mkdir");
+        tokio::fs::write(packs_dir.join("a.toml"), "[[comments]]\ntext = \"box\"\n")
+            .await
+            .expect("write pack");
+        let file = dir.path().join("comments.txt");
+        tokio::fs::write(&file, "# only a header\n\n")
+            .await
+            .expect("write comments");
+
+        let config = CommentsConfig {
+            file,
+            packs_dir,
+            ..CommentsConfig::default()
+        };
+        let comments = Comments::load(&config, Vec::new())
+            .await
+            .expect("the pack is usable");
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments.problems().len(), 1);
+        assert!(comments.problems()[0].ends_with("comments.txt: no comments"));
+    }
+
+    #[tokio::test]
+    async fn load_skips_invalid_packs_and_comments() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let packs_dir = dir.path().join("packs");
+        tokio::fs::create_dir(&packs_dir).await.expect("mkdir");
+        let file = dir.path().join("comments.txt");
+        tokio::fs::write(&file, "fine {user}\nbad {nope}\n")
+            .await
+            .expect("write comments");
+        for (name, content) in [
+            ("a.toml", "[[comments]]\ntext = \"box\"\n"),
+            ("b.toml", "[[comments]]\ntext = "),
+            ("c.toml", "name = \"a\"\n[[comments]]\ntext = \"dup\"\n"),
+            (
+                "d.toml",
+                "[[comments]]\ntext = \"{bad}\"\n[[comments]]\ntext = \"ok\"\n",
+            ),
+        ] {
+            tokio::fs::write(packs_dir.join(name), content)
+                .await
+                .expect("write pack");
+        }
+
+        let config = CommentsConfig {
+            file,
+            packs_dir,
+            ..CommentsConfig::default()
         };
-        assert_eq!(empty_comment.pick(), FALLBACK_COMMENTS[0]);
+        let comments = Comments::load(&config, Vec::new())
+            .await
+            .expect("valid comments are kept");
+        assert_eq!(
+            comments.pack_names().collect::<Vec<_>>(),
+            [DEFAULT_PACK, "a", "d"]
+        );
+        assert_eq!(comments.len(), 3);
+        let problems = comments.problems();
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(problems.iter().any(|p| p.contains("b.toml")));
+        assert!(problems.iter().any(|p| p.contains("duplicate")));
+        assert!(problems.iter().any(|p| p.contains("comments.txt: line 2")));
+        assert!(problems.iter().any(|p| p.contains("pack `d`: line 2")));
     }
 }
@@ -1,55 +1,256 @@
-use crate::error::{Error, Result};
-use std::{env, fmt::Debug, path::PathBuf, sync::OnceLock};
-use teloxide::types::ChatId;
-
-pub const FAILED_FETCH_MEDIA_MESSAGE: &str = "Failed to fetch media, you foking donkey.";
+use crate::{
+    caption::CaptionFormat,
+    error::{Error, Result},
+    i18n::DEFAULT_LANGUAGE,
+};
+use std::{
+    env,
+    fmt::Debug,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::OnceLock,
+    time::Duration,
+};
+use teloxide::types::{ChatId, UserId};
+use url::Url;
 
 static GLOBAL_CONFIG: OnceLock<Config> = OnceLock::new();
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub chat_id: Option<ChatId>,
+    /// Users allowed to run bot-wide admin commands (besides anyone in `chat_id`).
+    pub admin_users: Vec<UserId>,
+    /// Chat media is uploaded to so inline answers can reuse its `file_id`.
+    /// Inline mode is disabled when `None`.
+    pub inline_cache_chat: Option<ChatId>,
     pub youtube: YoutubeConfig,
     pub instagram: InstagramConfig,
     pub tiktok: TiktokConfig,
     pub twitter: TwitterConfig,
+    pub rate_limit: RateLimitConfig,
+    /// Webhook settings; `None` means long polling.
+    pub webhook: Option<WebhookConfig>,
+    /// How long in-flight jobs may keep running after SIGTERM/SIGINT.
+    pub shutdown_grace: Duration,
+    /// Address for the Prometheus `/metrics` endpoint; disabled when `None`.
+    pub metrics_address: Option<SocketAddr>,
+    /// Directory for state that must survive restarts (chat settings, ...).
+    pub data_dir: PathBuf,
+    pub comments: CommentsConfig,
+    pub caption: CaptionConfig,
+    /// Language for chats and senders without a supported one.
+    pub language: String,
+    /// Directory of `<lang>.ftl` files adding or overriding languages.
+    pub locales_dir: PathBuf,
+    /// Minimum time between two `/curse`s aimed at the same user in a chat.
+    pub curse_cooldown: Duration,
+    /// Identical failures within this window are coalesced into one admin
+    /// report.
+    pub report_window: Duration,
+    /// How often the cookie files are checked for expiring sessions.
+    pub cookie_check_interval: Duration,
+    /// Warn the admin chat this long before session cookies expire.
+    pub cookie_expiry_warning: Duration,
+    /// How long a cookie account is skipped after a rate limit or login
+    /// failure.
+    pub cookie_cooldown: Duration,
+}
+
+/// Where comments are loaded from.
+#[derive(Debug, Clone)]
+pub struct CommentsConfig {
+    /// Plaintext file with one template per line, loaded as the `default` pack.
+    pub file: PathBuf,
+    /// Directory of `*.toml` comment packs.
+    pub packs_dir: PathBuf,
+    /// Offset from UTC used for time-of-day tags.
+    pub utc_offset_hours: i32,
+    pub selection: CommentSelection,
+    /// Word stems that mark a comment as profane (skipped in clean mode).
+    pub profanity: Vec<String>,
+    pub generator: GeneratorMode,
+}
+
+/// What goes into media captions besides the comment.
+#[derive(Debug, Clone, Default)]
+pub struct CaptionConfig {
+    pub format: CaptionFormat,
+    /// Add the roleplay disclaimer.
+    pub disclaimer: bool,
+    /// Add title, uploader and duration of the media.
+    pub metadata: bool,
+    /// Link the original post.
+    pub source_link: bool,
+}
+
+/// How comments are drawn for a chat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentSelection {
+    /// Weighted sampling with replacement.
+    Random,
+    /// Every matching comment once (ignoring weights) before any repeats.
+    #[default]
+    Shuffle,
+    /// Weighted sampling skipping the last `n` comments shown in the chat.
+    NoRepeat(usize),
+}
+
+/// Where comments generated from a Markov chain over the loaded comments
+/// are used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeneratorMode {
+    /// No chain is built.
+    #[default]
+    Off,
+    /// Only for `/curse generated`.
+    OnDemand,
+    /// Also for captions and `/curse`, picking a comment when generation fails.
+    Captions,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Local address the embedded HTTP server binds to.
+    pub address: SocketAddr,
+    /// Public URL Telegram delivers updates to (e.g. behind a reverse proxy).
+    pub url: Url,
+    /// Value expected in the `X-Telegram-Bot-Api-Secret-Token` header.
+    /// A random token is generated when unset.
+    pub secret_token: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct YoutubeConfig {
-    pub cookies_path: Option<PathBuf>,
+    /// Cookie files, one per account.
+    pub cookies_paths: Vec<PathBuf>,
     pub postprocessor_args: String,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct InstagramConfig {
-    pub cookies_path: Option<PathBuf>,
+    /// Cookie files, one per account.
+    pub cookies_paths: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct TiktokConfig {
-    pub cookies_path: Option<PathBuf>,
+    /// Cookie files, one per account.
+    pub cookies_paths: Vec<PathBuf>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct TwitterConfig {
-    pub cookies_path: Option<PathBuf>,
+    /// Cookie files, one per account.
+    pub cookies_paths: Vec<PathBuf>,
+}
+
+/// Token-bucket limits applied before a handler runs. `None` disables a scope.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitConfig {
+    pub per_user: Option<RateLimitRule>,
+    pub per_chat: Option<RateLimitRule>,
+    pub per_platform: Option<RateLimitRule>,
+}
+
+/// Bucket of `capacity` tokens that refills completely over `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitRule {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+/// Log output settings. Read separately from [`Config`] because logging is
+/// set up before anything else.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Write to stdout.
+    pub stdout: bool,
+    /// Directory for daily rotated log files; file logging is off when `None`.
+    pub dir: Option<PathBuf>,
+    /// Number of daily log files to keep (at least one).
+    pub retention_days: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Single-line human readable output.
+    #[default]
+    Text,
+    /// Multi-line human readable output.
+    Pretty,
+    /// Newline-delimited JSON.
+    Json,
 }
 
 impl Config {
+    const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(25);
+    const DEFAULT_DATA_DIR: &'static str = "data";
+    const DEFAULT_LOCALES_DIR: &'static str = "locales";
+    const DEFAULT_CURSE_COOLDOWN: Duration = Duration::from_mins(5);
+    const DEFAULT_REPORT_WINDOW: Duration = Duration::from_hours(1);
+    const DEFAULT_COOKIE_CHECK_INTERVAL: Duration = Duration::from_hours(6);
+    const DEFAULT_COOKIE_EXPIRY_WARNING: Duration = Duration::from_hours(72);
+    const DEFAULT_COOKIE_COOLDOWN: Duration = Duration::from_mins(30);
+
     /// Load configuration from environment variables.
     #[must_use]
     pub fn from_env() -> Self {
-        let chat_id = env::var("CHAT_ID")
-            .ok()
-            .and_then(|id| id.parse::<i64>().ok())
-            .map(ChatId);
+        let chat_id = get_chat_id_from_env("CHAT_ID");
         Self {
             chat_id,
+            admin_users: env::var("ADMIN_USER_IDS")
+                .map(|ids| {
+                    ids.split(',')
+                        .filter_map(|id| id.trim().parse().ok())
+                        .map(UserId)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            inline_cache_chat: get_chat_id_from_env("INLINE_CACHE_CHAT_ID"),
             youtube: YoutubeConfig::from_env(),
             instagram: InstagramConfig::from_env(),
             tiktok: TiktokConfig::from_env(),
             twitter: TwitterConfig::from_env(),
+            rate_limit: RateLimitConfig::from_env(),
+            webhook: WebhookConfig::from_env(),
+            shutdown_grace: env::var("SHUTDOWN_GRACE_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(Self::DEFAULT_SHUTDOWN_GRACE, Duration::from_secs),
+            metrics_address: env::var("METRICS_ADDR")
+                .ok()
+                .and_then(|addr| addr.parse().ok()),
+            data_dir: env::var("DATA_DIR")
+                .map_or_else(|_| Self::DEFAULT_DATA_DIR.into(), PathBuf::from),
+            comments: CommentsConfig::from_env(),
+            caption: CaptionConfig::from_env(),
+            language: env::var("DEFAULT_LANGUAGE").unwrap_or_else(|_| DEFAULT_LANGUAGE.into()),
+            locales_dir: env::var("LOCALES_DIR")
+                .map_or_else(|_| Self::DEFAULT_LOCALES_DIR.into(), PathBuf::from),
+            curse_cooldown: env::var("CURSE_COOLDOWN_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(Self::DEFAULT_CURSE_COOLDOWN, Duration::from_secs),
+            report_window: env::var("ADMIN_REPORT_WINDOW_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(Self::DEFAULT_REPORT_WINDOW, Duration::from_secs),
+            cookie_check_interval: env::var("COOKIE_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .filter(|&secs| secs > 0)
+                .map_or(Self::DEFAULT_COOKIE_CHECK_INTERVAL, Duration::from_secs),
+            cookie_expiry_warning: env::var("COOKIE_EXPIRY_WARNING_DAYS")
+                .ok()
+                .and_then(|days| days.parse::<u64>().ok())
+                .and_then(|days| days.checked_mul(86_400))
+                .map_or(Self::DEFAULT_COOKIE_EXPIRY_WARNING, Duration::from_secs),
+            cookie_cooldown: env::var("COOKIE_COOLDOWN_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(Self::DEFAULT_COOKIE_COOLDOWN, Duration::from_secs),
         }
     }
 
@@ -80,7 +281,7 @@ impl YoutubeConfig {
 
     fn from_env() -> Self {
         Self {
-            cookies_path: get_path_from_env("YOUTUBE_SESSION_COOKIE_PATH"),
+            cookies_paths: get_paths_from_env("YOUTUBE_SESSION_COOKIE_PATH"),
             postprocessor_args: env::var("YOUTUBE_POSTPROCESSOR_ARGS")
                 .unwrap_or_else(|_| Self::DEFAULT_POSTPROCESSOR_ARGS.to_string()),
         }
@@ -90,7 +291,7 @@ impl YoutubeConfig {
 impl InstagramConfig {
     fn from_env() -> Self {
         Self {
-            cookies_path: get_path_from_env("IG_SESSION_COOKIE_PATH"),
+            cookies_paths: get_paths_from_env("IG_SESSION_COOKIE_PATH"),
         }
     }
 }
@@ -98,7 +299,7 @@ impl InstagramConfig {
 impl TiktokConfig {
     fn from_env() -> Self {
         Self {
-            cookies_path: get_path_from_env("TIKTOK_SESSION_COOKIE_PATH"),
+            cookies_paths: get_paths_from_env("TIKTOK_SESSION_COOKIE_PATH"),
         }
     }
 }
@@ -106,22 +307,281 @@ impl TiktokConfig {
 impl TwitterConfig {
     fn from_env() -> Self {
         Self {
-            cookies_path: get_path_from_env("TWITTER_SESSION_COOKIE_PATH"),
+            cookies_paths: get_paths_from_env("TWITTER_SESSION_COOKIE_PATH"),
         }
     }
 }
 
-fn get_path_from_env(key: &str) -> Option<PathBuf> {
+impl CommentsConfig {
+    const DEFAULT_FILE: &'static str = "comments.txt";
+    const DEFAULT_PACKS_DIR: &'static str = "packs";
+    const DEFAULT_PROFANITY: &'static [&'static str] =
+        &["fok", "fuck", "shit", "wank", "bastard", "bitch", "crap"];
+
+    fn from_env() -> Self {
+        Self {
+            file: env::var("COMMENTS_FILE")
+                .map_or_else(|_| Self::DEFAULT_FILE.into(), PathBuf::from),
+            packs_dir: env::var("COMMENT_PACKS_DIR")
+                .map_or_else(|_| Self::DEFAULT_PACKS_DIR.into(), PathBuf::from),
+            utc_offset_hours: env::var("UTC_OFFSET_HOURS")
+                .ok()
+                .and_then(|hours| hours.parse().ok())
+                .filter(|hours: &i32| (-12..=14).contains(hours))
+                .unwrap_or_default(),
+            selection: CommentSelection::from_env(),
+            generator: match env::var("COMMENT_GENERATOR").as_deref() {
+                Ok("on") => GeneratorMode::OnDemand,
+                Ok("captions") => GeneratorMode::Captions,
+                _ => GeneratorMode::Off,
+            },
+            profanity: env::var("PROFANITY_WORDS").map_or_else(
+                |_| Self::default_profanity(),
+                |words| {
+                    words
+                        .split(',')
+                        .map(|word| word.trim().to_lowercase())
+                        .filter(|word| !word.is_empty())
+                        .collect()
+                },
+            ),
+        }
+    }
+
+    fn default_profanity() -> Vec<String> {
+        Self::DEFAULT_PROFANITY
+            .iter()
+            .map(|&word| word.to_owned())
+            .collect()
+    }
+}
+
+impl CaptionConfig {
+    /// `CAPTION_FORMAT` is `plain`, `html` or `markdownv2`.
+    fn from_env() -> Self {
+        let format = match env::var("CAPTION_FORMAT").as_deref() {
+            Ok("html") => CaptionFormat::Html,
+            Ok("markdownv2") => CaptionFormat::MarkdownV2,
+            _ => CaptionFormat::Plain,
+        };
+        Self {
+            format,
+            disclaimer: get_flag_from_env("CAPTION_DISCLAIMER"),
+            metadata: get_flag_from_env("CAPTION_METADATA"),
+            source_link: get_flag_from_env("CAPTION_SOURCE_LINK"),
+        }
+    }
+}
+
+impl CommentSelection {
+    const DEFAULT_NO_REPEAT: usize = 10;
+
+    /// `COMMENT_SELECTION` is `random`, `shuffle` or `norepeat`; the window
+    /// for `norepeat` is `COMMENT_NO_REPEAT`.
+    fn from_env() -> Self {
+        match env::var("COMMENT_SELECTION").as_deref() {
+            Ok("random") => Self::Random,
+            Ok("norepeat") => Self::NoRepeat(
+                env::var("COMMENT_NO_REPEAT")
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(Self::DEFAULT_NO_REPEAT),
+            ),
+            _ => Self::Shuffle,
+        }
+    }
+}
+
+impl Default for CommentsConfig {
+    fn default() -> Self {
+        Self {
+            file: Self::DEFAULT_FILE.into(),
+            packs_dir: Self::DEFAULT_PACKS_DIR.into(),
+            utc_offset_hours: 0,
+            selection: CommentSelection::default(),
+            profanity: Self::default_profanity(),
+            generator: GeneratorMode::default(),
+        }
+    }
+}
+
+impl LogConfig {
+    const DEFAULT_RETENTION_DAYS: usize = 7;
+
+    /// Load log settings from environment variables.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let format = match env::var("LOG_FORMAT").as_deref() {
+            Ok("json") => LogFormat::Json,
+            Ok("pretty") => LogFormat::Pretty,
+            _ => LogFormat::Text,
+        };
+        Self {
+            format,
+            stdout: env::var("LOG_STDOUT").map_or(true, |v| v != "false" && v != "0"),
+            dir: env::var("LOG_DIR").ok().map(PathBuf::from),
+            retention_days: env::var("LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|days| days.parse().ok())
+                .filter(|&days| days > 0)
+                .unwrap_or(Self::DEFAULT_RETENTION_DAYS),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            stdout: true,
+            dir: None,
+            retention_days: Self::DEFAULT_RETENTION_DAYS,
+        }
+    }
+}
+
+impl WebhookConfig {
+    const DEFAULT_ADDRESS: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8080);
+
+    /// Webhook mode is enabled by setting `WEBHOOK_URL`.
+    fn from_env() -> Option<Self> {
+        let url = env::var("WEBHOOK_URL").ok()?;
+        let url = match Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => {
+                tracing::warn!(url, "invalid WEBHOOK_URL, falling back to polling: {e}");
+                return None;
+            }
+        };
+        let address = env::var("WEBHOOK_BIND_ADDR")
+            .ok()
+            .map_or(Self::DEFAULT_ADDRESS, |addr| match addr.parse() {
+                Ok(address) => address,
+                Err(e) => {
+                    tracing::warn!(
+                        addr,
+                        default = %Self::DEFAULT_ADDRESS,
+                        "invalid WEBHOOK_BIND_ADDR, using the default: {e}"
+                    );
+                    Self::DEFAULT_ADDRESS
+                }
+            });
+        let secret_token = env::var("WEBHOOK_SECRET_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        Some(Self {
+            address,
+            url,
+            secret_token,
+        })
+    }
+}
+
+impl RateLimitConfig {
+    fn from_env() -> Self {
+        Self {
+            per_user: get_rate_limit_from_env("RATE_LIMIT_PER_USER"),
+            per_chat: get_rate_limit_from_env("RATE_LIMIT_PER_CHAT"),
+            per_platform: get_rate_limit_from_env("RATE_LIMIT_PER_PLATFORM"),
+        }
+    }
+}
+
+impl RateLimitRule {
+    /// Parse a `<capacity>/<seconds>` spec, e.g. `5/60` for five links a minute.
+    #[must_use]
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (capacity, secs) = spec.trim().split_once('/')?;
+        let capacity = capacity.trim().parse::<u32>().ok().filter(|c| *c > 0)?;
+        let secs = secs.trim().parse::<u64>().ok().filter(|s| *s > 0)?;
+        Some(Self {
+            capacity,
+            period: Duration::from_secs(secs),
+        })
+    }
+
+    /// Tokens regained per second.
+    #[inline]
+    #[must_use]
+    pub fn refill_per_sec(self) -> f64 {
+        f64::from(self.capacity) / self.period.as_secs_f64()
+    }
+}
+
+fn get_rate_limit_from_env(key: &str) -> Option<RateLimitRule> {
+    let spec = env::var(key).ok()?;
+    let rule = RateLimitRule::parse(&spec);
+    if rule.is_none() {
+        tracing::warn!(
+            key,
+            spec,
+            "invalid rate limit, expected `<capacity>/<seconds>`"
+        );
+    }
+    rule
+}
+
+fn get_chat_id_from_env(key: &str) -> Option<ChatId> {
     env::var(key)
         .ok()
+        .and_then(|id| id.parse::<i64>().ok())
+        .map(ChatId)
+}
+
+fn get_flag_from_env(key: &str) -> bool {
+    env::var(key).is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes" | "on"))
+}
+
+/// Comma-separated list of files. Missing files keep their position so
+/// account numbers match the list; they can be uploaded with `/cookies`.
+fn get_paths_from_env(key: &str) -> Vec<PathBuf> {
+    env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
         .map(PathBuf::from)
-        .filter(|p| p.is_file())
+        .inspect(|p| {
+            if !p.is_file() {
+                tracing::warn!(key, path = %p.display(), "cookie file not found");
+            }
+        })
+        .collect()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            chat_id: None,
+            admin_users: Vec::new(),
+            inline_cache_chat: None,
+            youtube: YoutubeConfig::default(),
+            instagram: InstagramConfig::default(),
+            tiktok: TiktokConfig::default(),
+            twitter: TwitterConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            webhook: None,
+            shutdown_grace: Self::DEFAULT_SHUTDOWN_GRACE,
+            metrics_address: None,
+            data_dir: Self::DEFAULT_DATA_DIR.into(),
+            comments: CommentsConfig::default(),
+            caption: CaptionConfig::default(),
+            language: DEFAULT_LANGUAGE.into(),
+            locales_dir: Self::DEFAULT_LOCALES_DIR.into(),
+            curse_cooldown: Self::DEFAULT_CURSE_COOLDOWN,
+            report_window: Self::DEFAULT_REPORT_WINDOW,
+            cookie_check_interval: Self::DEFAULT_COOKIE_CHECK_INTERVAL,
+            cookie_expiry_warning: Self::DEFAULT_COOKIE_EXPIRY_WARNING,
+            cookie_cooldown: Self::DEFAULT_COOKIE_COOLDOWN,
+        }
+    }
 }
 
 impl Default for YoutubeConfig {
     fn default() -> Self {
         Self {
-            cookies_path: None,
+            cookies_paths: Vec::new(),
             postprocessor_args: Self::DEFAULT_POSTPROCESSOR_ARGS.into(),
         }
     }
@@ -0,0 +1,92 @@
+use crate::{i18n::global_catalog, settings::global_settings, template::CaptionContext};
+use teloxide::{
+    prelude::*,
+    requests::Requester,
+    types::{MessageId, ReplyParameters, ThreadId},
+};
+
+/// Where the answers to one incoming message go.
+///
+/// Every send for a message (media, errors, status) goes through this so it
+/// lands in the same forum topic and replies to the source message.
+#[derive(Debug, Clone)]
+pub struct MessageContext {
+    pub chat_id: ChatId,
+    /// Message to reply to; `None` when the source is going to be deleted.
+    pub reply_to: Option<MessageId>,
+    /// Forum topic of the source message.
+    pub thread_id: Option<ThreadId>,
+    /// Credit line prepended to media captions (replace mode).
+    pub attribution: Option<String>,
+    /// Send media behind a spoiler.
+    pub spoiler: bool,
+    /// Values for comment placeholders.
+    pub caption: CaptionContext,
+    /// Language for replies.
+    pub lang: String,
+    /// Use the polite variants of messages (clean mode).
+    pub clean: bool,
+}
+
+impl MessageContext {
+    /// Context replying to `msg` inside its forum topic (if any).
+    #[must_use]
+    pub fn from_message(msg: &Message) -> Self {
+        let settings = global_settings().get(msg.chat.id);
+        let lang = global_catalog().resolve(
+            settings.language.as_deref(),
+            msg.from.as_ref().and_then(|u| u.language_code.as_deref()),
+        );
+        Self {
+            chat_id: msg.chat.id,
+            reply_to: Some(msg.id),
+            thread_id: msg.thread_id.filter(|_| msg.is_topic_message),
+            attribution: None,
+            spoiler: false,
+            caption: CaptionContext {
+                packs: settings.packs,
+                lang: Some(lang.clone()),
+                clean: settings.clean,
+                ..CaptionContext::from_message(msg)
+            },
+            lang,
+            clean: settings.clean,
+        }
+    }
+
+    /// Message `key` in the context's language, polite in clean mode.
+    #[must_use]
+    pub fn message(&self, key: &str) -> String {
+        let catalog = global_catalog();
+        if self.clean {
+            catalog.get_clean(&self.lang, key, None)
+        } else {
+            catalog.get(&self.lang, key, None)
+        }
+    }
+
+    /// Reply parameters for the source message; sending still succeeds if
+    /// it was deleted in the meantime.
+    #[must_use]
+    pub fn reply_parameters(&self) -> Option<ReplyParameters> {
+        self.reply_to
+            .map(|id| ReplyParameters::new(id).allow_sending_without_reply())
+    }
+
+    /// Build a text message request in this context.
+    #[must_use]
+    pub fn send_message(
+        &self,
+        bot: &Bot,
+        text: impl Into<String>,
+    ) -> <Bot as Requester>::SendMessage {
+        let mut request = bot.send_message(self.chat_id, text);
+        if let Some(thread_id) = self.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+        if let Some(reply) = self.reply_parameters() {
+            request = request.reply_parameters(reply);
+        }
+        request
+    }
+}
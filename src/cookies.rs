@@ -0,0 +1,659 @@
+use crate::{
+    config::Config,
+    error::{Error, Result},
+};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
+use tempfile::{TempDir, tempdir};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{debug, warn};
+
+/// Header yt-dlp expects on a Netscape cookie file.
+const HEADER: &str = "# Netscape HTTP Cookie File";
+/// Prefix browsers put on the domain of `HttpOnly` cookies.
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// A platform whose yt-dlp cookies can be replaced at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Instagram,
+    Tiktok,
+    Twitter,
+    Youtube,
+}
+
+impl Platform {
+    pub const ALL: [Self; 4] = [Self::Instagram, Self::Tiktok, Self::Twitter, Self::Youtube];
+
+    /// Parse a platform name as used by the handlers (also `ig` and `x`).
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "instagram" | "ig" => Some(Self::Instagram),
+            "tiktok" => Some(Self::Tiktok),
+            "twitter" | "x" => Some(Self::Twitter),
+            "youtube" | "yt" => Some(Self::Youtube),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Instagram => "instagram",
+            Self::Tiktok => "tiktok",
+            Self::Twitter => "twitter",
+            Self::Youtube => "youtube",
+        }
+    }
+
+    /// Environment variable that sets the cookie file path.
+    #[must_use]
+    pub const fn env_var(self) -> &'static str {
+        match self {
+            Self::Instagram => "IG_SESSION_COOKIE_PATH",
+            Self::Tiktok => "TIKTOK_SESSION_COOKIE_PATH",
+            Self::Twitter => "TWITTER_SESSION_COOKIE_PATH",
+            Self::Youtube => "YOUTUBE_SESSION_COOKIE_PATH",
+        }
+    }
+
+    /// Cookie files yt-dlp is pointed at for this platform, one per account.
+    #[must_use]
+    pub fn cookies_paths(self, config: &Config) -> &[PathBuf] {
+        match self {
+            Self::Instagram => &config.instagram.cookies_paths,
+            Self::Tiktok => &config.tiktok.cookies_paths,
+            Self::Twitter => &config.twitter.cookies_paths,
+            Self::Youtube => &config.youtube.cookies_paths,
+        }
+    }
+
+    /// Domains the platform's cookies are set on.
+    const fn domains(self) -> &'static [&'static str] {
+        match self {
+            Self::Instagram => &["instagram.com"],
+            Self::Tiktok => &["tiktok.com"],
+            Self::Twitter => &["x.com", "twitter.com"],
+            Self::Youtube => &["youtube.com", "google.com"],
+        }
+    }
+
+    /// Cookies that carry the logged-in session.
+    const fn session_cookies(self) -> &'static [&'static str] {
+        match self {
+            Self::Instagram => &["sessionid"],
+            Self::Tiktok => &["sessionid", "sessionid_ss"],
+            Self::Twitter => &["auth_token"],
+            Self::Youtube => &["__Secure-3PSID", "SID", "LOGIN_INFO"],
+        }
+    }
+
+    fn owns(self, domain: &str) -> bool {
+        let domain = domain.trim_start_matches('.').to_lowercase();
+        self.domains()
+            .iter()
+            .any(|d| domain == *d || domain.ends_with(&format!(".{d}")))
+    }
+}
+
+/// One line of a Netscape cookie file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub domain: String,
+    pub name: String,
+    /// Unix time; `0` for a cookie that lasts for the browser session.
+    pub expires: u64,
+}
+
+/// When a session cookie expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// Ends with the browser session; yt-dlp keeps using it.
+    Session,
+    At(u64),
+    Expired(u64),
+}
+
+/// Summary of a validated cookie file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieReport {
+    /// Cookies for the platform.
+    pub cookies: usize,
+    /// Cookies for other sites, kept as they are.
+    pub foreign: usize,
+    /// Session cookies by name.
+    pub sessions: Vec<(String, Expiry)>,
+}
+
+/// Parse the cookies of a Netscape cookie file.
+///
+/// # Errors
+///
+/// Returns `Error::ValidationFailed` naming the first malformed line, or
+/// when the file has no cookies.
+pub fn parse(text: &str) -> Result<Vec<Cookie>> {
+    let mut cookies = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let line = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let invalid = |what: &str| Error::validation_falied(format!("line {}: {what}", i + 1));
+        let [domain, subdomains, _path, secure, expires, name, _value] = fields[..] else {
+            return Err(invalid("expected 7 tab-separated fields"));
+        };
+        if domain.is_empty() || name.is_empty() {
+            return Err(invalid("empty domain or name"));
+        }
+        if ![subdomains, secure]
+            .iter()
+            .all(|flag| matches!(*flag, "TRUE" | "FALSE"))
+        {
+            return Err(invalid("flags must be TRUE or FALSE"));
+        }
+        let expires = expires
+            .parse::<f64>()
+            .ok()
+            .filter(|e| e.is_finite() && *e >= 0.0)
+            .ok_or_else(|| invalid("invalid expiry"))?;
+        cookies.push(Cookie {
+            domain: domain.to_owned(),
+            name: name.to_owned(),
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            expires: expires as u64,
+        });
+    }
+    if cookies.is_empty() {
+        return Err(Error::validation_falied("no cookies in file"));
+    }
+    Ok(cookies)
+}
+
+/// Check that `cookies` log in to `platform` at Unix time `now`.
+///
+/// # Errors
+///
+/// Returns `Error::ValidationFailed` when no cookie is for the platform, no
+/// session cookie is present or every session cookie has expired.
+pub fn validate(platform: Platform, cookies: &[Cookie], now: u64) -> Result<CookieReport> {
+    let own = cookies
+        .iter()
+        .filter(|c| platform.owns(&c.domain))
+        .collect::<Vec<_>>();
+    if own.is_empty() {
+        return Err(Error::validation_falied(format!(
+            "no cookies for {}",
+            platform.domains().join(" or ")
+        )));
+    }
+
+    let sessions = sessions(platform, cookies, now)?;
+    if sessions
+        .iter()
+        .all(|(_, e)| matches!(e, Expiry::Expired(_)))
+    {
+        return Err(Error::validation_falied("the session cookies have expired"));
+    }
+
+    Ok(CookieReport {
+        cookies: own.len(),
+        foreign: cookies.len() - own.len(),
+        sessions,
+    })
+}
+
+/// Expiry of the session cookies of `platform` at Unix time `now`.
+///
+/// # Errors
+///
+/// Returns `Error::ValidationFailed` when there is no session cookie.
+pub fn sessions(platform: Platform, cookies: &[Cookie], now: u64) -> Result<Vec<(String, Expiry)>> {
+    let sessions = cookies
+        .iter()
+        .filter(|c| platform.owns(&c.domain))
+        .filter(|c| platform.session_cookies().contains(&c.name.as_str()))
+        .map(|c| {
+            let expiry = match c.expires {
+                0 => Expiry::Session,
+                at if at <= now => Expiry::Expired(at),
+                at => Expiry::At(at),
+            };
+            (c.name.clone(), expiry)
+        })
+        .collect::<Vec<_>>();
+    if sessions.is_empty() {
+        return Err(Error::validation_falied(format!(
+            "no session cookie ({}); is the account logged in?",
+            platform.session_cookies().join(", ")
+        )));
+    }
+    Ok(sessions)
+}
+
+/// Validate `text` as cookies for `platform` and atomically replace the file
+/// at `path` with it. yt-dlp reads the file on every run, so the new cookies
+/// are used from the next download on.
+///
+/// # Errors
+///
+/// - Returns `Error::ValidationFailed` if the file is not usable (see
+///   [`parse`] and [`validate`]); the old file is kept.
+/// - Returns `Error::Io` if writing the file fails.
+pub async fn replace(
+    platform: Platform,
+    path: &Path,
+    text: &str,
+    now: u64,
+) -> Result<CookieReport> {
+    let report = validate(platform, &parse(text)?, now)?;
+    let mut content = text.replace("\r\n", "\n");
+    if !content.starts_with(HEADER) && !content.starts_with("# HTTP Cookie File") {
+        content = format!("{HEADER}\n{content}");
+    }
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    let lock = jar_lock(path);
+    let mut generation = lock.lock().await;
+    write_cookie_file(path, &content).await?;
+    // copies taken before this must not merge old cookies into the new file
+    *generation += 1;
+    drop(generation);
+    Ok(report)
+}
+
+/// A private copy of a cookie file for one yt-dlp run.
+///
+/// yt-dlp writes the cookies it updated back to the file it was given, so
+/// concurrent runs on the canonical file would overwrite each other's
+/// changes or interleave their writes.
+#[derive(Debug)]
+pub struct JarCopy {
+    dir: TempDir,
+    canonical: PathBuf,
+    /// Contents when copied.
+    base: String,
+    /// Replacements of the canonical file when copied.
+    generation: u64,
+}
+
+impl JarCopy {
+    /// Copy the cookie file at `canonical` into a new temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file cannot be read or the copy written.
+    pub async fn new(canonical: &Path) -> Result<Self> {
+        let lock = jar_lock(canonical);
+        let generation = lock.lock().await;
+        let base = fs::read_to_string(canonical).await?;
+        let copy = Self {
+            dir: tempdir()?,
+            canonical: canonical.to_path_buf(),
+            base,
+            generation: *generation,
+        };
+        drop(generation);
+        fs::write(copy.path(), &copy.base).await?;
+        Ok(copy)
+    }
+
+    /// The copy to pass to yt-dlp.
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("cookies.txt")
+    }
+
+    /// Merge the cookies the run changed into the canonical file, keeping
+    /// changes made there meanwhile by other runs. Nothing is merged when the
+    /// file was replaced (`/cookies`) since the copy was taken.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if reading the files or writing the merge fails.
+    pub async fn merge_back(self) -> Result<()> {
+        let ours = match fs::read_to_string(self.path()).await {
+            Ok(ours) => ours,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if ours == self.base {
+            return Ok(());
+        }
+
+        let lock = jar_lock(&self.canonical);
+        let generation = lock.lock().await;
+        if *generation != self.generation {
+            debug!(path = %self.canonical.display(), "cookie file replaced during the run, dropping its changes");
+            return Ok(());
+        }
+        let theirs = fs::read_to_string(&self.canonical).await?;
+        let merged = merge(&self.base, &ours, &theirs);
+        if merged != theirs {
+            write_cookie_file(&self.canonical, &merged).await?;
+        }
+        drop(generation);
+        Ok(())
+    }
+}
+
+/// Lock serializing access to the cookie file at `path`, holding the number
+/// of times it was replaced.
+fn jar_lock(path: &Path) -> Arc<tokio::sync::Mutex<u64>> {
+    static LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<u64>>>>> =
+        LazyLock::new(Mutex::default);
+    let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    Arc::clone(locks.entry(path.to_path_buf()).or_default())
+}
+
+/// Cookie identity: domain, path and name.
+type Key<'a> = (String, &'a str, &'a str);
+
+/// Cookie lines of `text` by identity, in file order. Comments and
+/// malformed lines are skipped.
+fn entries(text: &str) -> Vec<(Key<'_>, &str)> {
+    text.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter_map(|line| {
+            let cookie = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+            if cookie.starts_with('#') {
+                return None;
+            }
+            let fields = cookie.split('\t').collect::<Vec<_>>();
+            let [domain, _, path, _, _, name, _] = fields[..] else {
+                return None;
+            };
+            Some(((domain.to_lowercase(), path, name), line))
+        })
+        .collect()
+}
+
+/// Expiry field of a cookie line; session cookies (0) never expire.
+fn expires(line: &str) -> u64 {
+    let cookie = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+    match cookie.split('\t').nth(4).and_then(|e| e.parse().ok()) {
+        Some(0) => u64::MAX,
+        expires => expires.unwrap_or_default(),
+    }
+}
+
+/// Three-way merge of cookie files: cookies a run added, changed or removed
+/// (`base` to `ours`) are applied to the current file (`theirs`); every other
+/// cookie keeps its current value, which is the newest. A cookie both sides
+/// changed keeps the line that expires later.
+fn merge(base: &str, ours: &str, theirs: &str) -> String {
+    let base = entries(base).into_iter().collect::<HashMap<_, _>>();
+    let ours = entries(ours);
+    let mut merged = entries(theirs);
+
+    for (key, line) in &ours {
+        if base.get(key) == Some(line) {
+            continue;
+        }
+        match merged.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => {
+                let theirs_changed = base.get(key) != Some(&entry.1);
+                if !theirs_changed || expires(line) >= expires(entry.1) {
+                    entry.1 = line;
+                }
+            }
+            None => merged.push((key.clone(), line)),
+        }
+    }
+    // removed by the run (e.g. expired), unless changed meanwhile
+    merged.retain(|(key, line)| ours.iter().any(|(k, _)| k == key) || base.get(key) != Some(line));
+
+    let mut text = format!("{HEADER}\n");
+    for (_, line) in merged {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+/// Replace the file atomically, or in place when it is bind-mounted on its
+/// own (as in `docker-compose.yml`) and cannot be renamed over.
+async fn write_cookie_file(path: &Path, content: &str) -> Result<()> {
+    match write_private(path, content).await {
+        Err(Error::Io(e))
+            if matches!(
+                e.kind(),
+                ErrorKind::ResourceBusy | ErrorKind::CrossesDevices
+            ) =>
+        {
+            warn!(path = %path.display(), "cookie file is a mount point, overwriting it in place");
+            Ok(fs::write(path, content).await?)
+        }
+        result => result,
+    }
+}
+
+/// Write `content` next to `path` and rename it into place, like
+/// `settings::write_atomic`, keeping the permissions of the replaced file
+/// (owner-only for a new one) so the sessions never become world-readable.
+async fn write_private(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp).await;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)
+            .await
+            .map_or(0o600, |meta| meta.permissions().mode() & 0o777);
+        options.mode(mode);
+        mode
+    };
+
+    let written = async {
+        let mut file = options.open(&tmp).await?;
+        file.write_all(content.as_bytes()).await?;
+        file.flush().await?;
+        // the umask may have narrowed the mode on creation
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, std::fs::Permissions::from_mode(mode)).await?;
+        }
+        fs::rename(&tmp, path).await
+    }
+    .await;
+    if let Err(e) = written {
+        warn!(path = %path.display(), "failed to replace file: {e}");
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_800_000_000;
+
+    fn file(lines: &[&str]) -> String {
+        lines.join("\n")
+    }
+
+    #[test]
+    fn parses_and_validates_sessions() {
+        let text = file(&[
+            "# Netscape HTTP Cookie File",
+            "",
+            ".instagram.com\tTRUE\t/\tTRUE\t1900000000\tcsrftoken\tabc",
+            "#HttpOnly_.instagram.com\tTRUE\t/\tTRUE\t1900000000.5\tsessionid\t123%3A",
+            ".example.com\tTRUE\t/\tFALSE\t0\tother\tx",
+        ]);
+        let cookies = parse(&text).expect("valid file");
+        assert_eq!(cookies.len(), 3);
+
+        let report = validate(Platform::Instagram, &cookies, NOW).expect("logged in");
+        assert_eq!(report.cookies, 2);
+        assert_eq!(report.foreign, 1);
+        assert_eq!(
+            report.sessions,
+            [("sessionid".into(), Expiry::At(1_900_000_000))]
+        );
+
+        let err = validate(Platform::Tiktok, &cookies, NOW).expect_err("wrong platform");
+        assert!(err.to_string().contains("tiktok.com"));
+        let expired = validate(Platform::Instagram, &cookies, 1_950_000_000);
+        assert!(expired.is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let bad = [
+            "",
+            "# only comments",
+            ".x.com\tTRUE\t/\tTRUE\tauth_token\tabc",
+            ".x.com\tyes\t/\tTRUE\t0\tauth_token\tabc",
+            ".x.com\tTRUE\t/\tTRUE\tsoon\tauth_token\tabc",
+        ];
+        for text in bad {
+            assert!(parse(text).is_err(), "{text:?}");
+        }
+        let no_session = parse(".x.com\tTRUE\t/\tTRUE\t0\tct0\tabc").expect("valid line");
+        assert!(validate(Platform::Twitter, &no_session, NOW).is_err());
+    }
+
+    #[test]
+    fn merges_changes_of_a_run() {
+        let line = |name: &str, value: &str| format!(".x.com\tTRUE\t/\tTRUE\t0\t{name}\t{value}");
+        let base = file(&[HEADER, &line("a", "1"), &line("b", "1"), &line("c", "1")]);
+        // the run refreshed a, removed c and added d
+        let ours = file(&[&line("a", "2"), &line("b", "1"), &line("d", "1")]);
+        // another run meanwhile refreshed b and added e
+        let theirs = file(&[
+            HEADER,
+            &line("a", "1"),
+            &line("b", "3"),
+            &line("c", "1"),
+            &line("e", "1"),
+        ]);
+
+        let merged = merge(&base, &ours, &theirs);
+        let expected = [
+            HEADER,
+            &line("a", "2"),
+            &line("b", "3"),
+            &line("e", "1"),
+            &line("d", "1"),
+        ];
+        assert_eq!(merged, format!("{}\n", expected.join("\n")));
+        assert_eq!(merge(&base, &base, &theirs), format!("{theirs}\n"));
+    }
+
+    #[test]
+    fn merge_conflicts_keep_the_later_expiry() {
+        let line = |name: &str, expires: u64, value: &str| {
+            format!(".x.com\tTRUE\t/\tTRUE\t{expires}\t{name}\t{value}")
+        };
+        let base = file(&[HEADER, &line("a", 100, "1"), &line("b", 100, "1")]);
+        // both runs refreshed a and b; ours got the later a, theirs the later b
+        let ours = file(&[HEADER, &line("a", 300, "ours"), &line("b", 200, "ours")]);
+        let theirs = file(&[HEADER, &line("a", 200, "theirs"), &line("b", 300, "theirs")]);
+
+        let merged = merge(&base, &ours, &theirs);
+        let expected = [HEADER, &line("a", 300, "ours"), &line("b", 300, "theirs")];
+        assert_eq!(merged, format!("{}\n", expected.join("\n")));
+
+        // a session cookie outlives any expiry date
+        let session = file(&[HEADER, &line("a", 0, "ours")]);
+        assert!(merge(&base, &session, &theirs).contains(&line("a", 0, "ours")));
+    }
+
+    #[tokio::test]
+    async fn jar_copies_merge_back() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("cookies.txt");
+        let good = ".x.com\tTRUE\t/\tTRUE\t0\tauth_token\tabc\n";
+        replace(Platform::Twitter, &path, good, NOW)
+            .await
+            .expect("valid cookies");
+
+        let first = JarCopy::new(&path).await.expect("copy");
+        let second = JarCopy::new(&path).await.expect("copy");
+        assert_ne!(first.path(), second.path());
+        let updated = format!("{HEADER}\n.x.com\tTRUE\t/\tTRUE\t0\tauth_token\tnew\n");
+        std::fs::write(first.path(), &updated).expect("yt-dlp writes");
+        first.merge_back().await.expect("merge");
+        assert_eq!(std::fs::read_to_string(&path).expect("read"), updated);
+
+        // replaced while `second` ran: its changes are dropped
+        replace(Platform::Twitter, &path, good, NOW)
+            .await
+            .expect("valid cookies");
+        std::fs::write(second.path(), "# HTTP Cookie File\n").expect("yt-dlp writes");
+        second.merge_back().await.expect("merge");
+        assert!(
+            std::fs::read_to_string(&path)
+                .expect("read")
+                .contains("abc")
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_keeps_old_file_when_invalid() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("cookies.txt");
+        let good = ".x.com\tTRUE\t/\tTRUE\t0\tauth_token\tabc\r\n";
+
+        replace(Platform::Twitter, &path, good, NOW)
+            .await
+            .expect("valid cookies");
+        let written = std::fs::read_to_string(&path).expect("written");
+        assert_eq!(
+            written,
+            format!("{HEADER}\n.x.com\tTRUE\t/\tTRUE\t0\tauth_token\tabc\n")
+        );
+
+        assert!(
+            replace(Platform::Twitter, &path, "garbage", NOW)
+                .await
+                .is_err()
+        );
+        assert_eq!(std::fs::read_to_string(&path).expect("kept"), written);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn replace_keeps_file_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &Path| {
+            std::fs::metadata(path)
+                .expect("written")
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        let dir = tempfile::tempdir().expect("tempdir");
+        let good = ".x.com\tTRUE\t/\tTRUE\t0\tauth_token\tabc\n";
+
+        let new = dir.path().join("new.txt");
+        replace(Platform::Twitter, &new, good, NOW)
+            .await
+            .expect("valid cookies");
+        assert_eq!(mode(&new), 0o600);
+
+        let shared = dir.path().join("shared.txt");
+        std::fs::write(&shared, "").expect("create");
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o640)).expect("chmod");
+        replace(Platform::Twitter, &shared, good, NOW)
+            .await
+            .expect("valid cookies");
+        assert_eq!(mode(&shared), 0o640);
+    }
+}
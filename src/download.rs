@@ -1,23 +1,29 @@
 use crate::config::global_config;
 use crate::{
+    accounts::global_accounts,
+    context::MessageContext,
+    cookies::{JarCopy, Platform},
     error::{Error, Result},
+    metrics::{GaugeGuard, metrics},
     utils::{
         IMAGE_EXTSTENSIONS, MediaKind, VIDEO_EXTSTENSIONS, detect_media_kind_async,
         send_media_from_path,
     },
 };
 use futures::{StreamExt, stream};
+use serde::Deserialize;
 use std::{
     cmp::min,
     ffi::OsStr,
     fs,
     path::{Path, PathBuf},
     process::Stdio,
+    time::Instant,
 };
-use teloxide::{Bot, types::ChatId};
+use teloxide::Bot;
 use tempfile::{TempDir, tempdir};
 use tokio::{fs::read_dir, process::Command};
-use tracing::{debug, warn};
+use tracing::{Span, debug, field::Empty, info, instrument, warn};
 
 const FORBIDDEN_EXTENSIONS: &[&str] = &["json", "txt", "log"];
 
@@ -27,6 +33,30 @@ const FORBIDDEN_EXTENSIONS: &[&str] = &["json", "txt", "log"];
 pub struct DownloadResult {
     pub tempdir: TempDir,
     pub files: Vec<PathBuf>,
+    /// Metadata from yt-dlp's `.info.json`, when one was written.
+    pub info: Option<MediaInfo>,
+}
+
+/// The parts of yt-dlp's `.info.json` the bot cares about.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MediaInfo {
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    /// Length in seconds.
+    pub duration: Option<f64>,
+    /// Minimum viewer age; 18 for adult content.
+    pub age_limit: Option<u8>,
+}
+
+impl MediaInfo {
+    const ADULT_AGE: u8 = 18;
+
+    /// Whether the source marks the media as adults only.
+    #[must_use]
+    pub fn is_age_restricted(&self) -> bool {
+        self.age_limit.is_some_and(|age| age >= Self::ADULT_AGE)
+    }
 }
 
 /// Run a command in a freshly created temporary directory and collect
@@ -43,23 +73,41 @@ pub struct DownloadResult {
 /// - `Error::Other` for non-zero exit code (with stderr).
 /// - `Error::NoMediaFound` if no files were produced.
 #[allow(clippy::similar_names)]
+#[instrument(skip_all, fields(cmd = %cmd, args = ?args, status = Empty))]
 async fn run_command_in_tempdir(cmd: &str, args: &[&str]) -> Result<DownloadResult> {
     let tmp = tempdir()?;
     let cwd = tmp.path().to_path_buf();
 
+    let (_in_flight, duration) = match cmd {
+        "yt-dlp" => (
+            Some(GaugeGuard::new(&metrics().ytdlp_in_flight)),
+            Some(&metrics().download_duration),
+        ),
+        "ffmpeg" => (None, Some(&metrics().transcode_duration)),
+        _ => (None, None),
+    };
+    let started = Instant::now();
+
     let output = Command::new(cmd)
         .current_dir(&cwd)
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .output()
         .await?;
 
+    if let Some(duration) = duration {
+        duration.observe(started.elapsed().as_secs_f64());
+    }
+    Span::current().record("status", tracing::field::display(output.status));
+    info!(status = %output.status, elapsed = ?started.elapsed(), "{cmd} exited");
+
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
         let err = match cmd {
-            "yt-dlp" => Error::ytdlp_failed(stderr),
+            "yt-dlp" => Error::ytdlp_failed(output.status.code(), stderr),
             _ => Error::Other(format!("{cmd} failed: {stderr}")),
         };
         return Err(err);
@@ -95,9 +143,26 @@ async fn run_command_in_tempdir(cmd: &str, args: &[&str]) -> Result<DownloadResu
     Ok(DownloadResult {
         tempdir: tmp,
         files,
+        info: None,
     })
 }
 
+/// Parse the first `*.info.json` in `dir`, if any.
+async fn read_info_json(dir: &Path) -> Option<MediaInfo> {
+    let mut rd = read_dir(dir).await.ok()?;
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        let path = entry.path();
+        if !path.to_string_lossy().ends_with(".info.json") {
+            continue;
+        }
+        let content = tokio::fs::read(&path).await.ok()?;
+        return serde_json::from_slice(&content)
+            .inspect_err(|e| warn!(path = %path.display(), "invalid info json: {e}"))
+            .ok();
+    }
+    None
+}
+
 /// Download a Instagram URL with yt-dlp.
 ///
 /// # Errors
@@ -105,8 +170,7 @@ async fn run_command_in_tempdir(cmd: &str, args: &[&str]) -> Result<DownloadResu
 /// - Propagates `run_command_in_tempdir` errors.
 #[cfg(feature = "instagram")]
 pub async fn download_instagram(url: String) -> Result<DownloadResult> {
-    let config = global_config();
-    run_yt_dlp(&["-t", "mp4"], config.instagram.cookies_path.as_ref(), &url).await
+    run_yt_dlp(&["-t", "mp4"], Platform::Instagram, &url).await
 }
 
 /// Download a Tiktok URL with yt-dlp.
@@ -116,8 +180,7 @@ pub async fn download_instagram(url: String) -> Result<DownloadResult> {
 /// - Propagates `run_command_in_tempdir` errors.
 #[cfg(feature = "tiktok")]
 pub async fn download_tiktok(url: String) -> Result<DownloadResult> {
-    let config = global_config();
-    run_yt_dlp(&["-t", "mp4"], config.tiktok.cookies_path.as_ref(), &url).await
+    run_yt_dlp(&["-t", "mp4"], Platform::Tiktok, &url).await
 }
 
 /// Download a Twitter URL with yt-dlp.
@@ -127,8 +190,7 @@ pub async fn download_tiktok(url: String) -> Result<DownloadResult> {
 /// - Propagates `run_command_in_tempdir` errors.
 #[cfg(feature = "twitter")]
 pub async fn download_twitter(url: String) -> Result<DownloadResult> {
-    let config = global_config();
-    run_yt_dlp(&["-t", "mp4"], config.twitter.cookies_path.as_ref(), &url).await
+    run_yt_dlp(&["-t", "mp4"], Platform::Twitter, &url).await
 }
 
 /// Download a URL with yt-dlp.
@@ -149,7 +211,7 @@ pub async fn download_youtube(url: String) -> Result<DownloadResult> {
     if !config.youtube.postprocessor_args.is_empty() {
         args.extend(["--postprocessor-args", &config.youtube.postprocessor_args]);
     }
-    run_yt_dlp(&args, config.youtube.cookies_path.as_ref(), &url).await
+    run_yt_dlp(&args, Platform::Youtube, &url).await
 }
 
 /// Post-process a `DownloadResult`.
@@ -160,11 +222,25 @@ pub async fn download_youtube(url: String) -> Result<DownloadResult> {
 /// # Errors
 ///
 /// - Propagates `send_media_from_path` errors or returns NoMediaFound/UnknownMediaKind.
+#[instrument(skip_all, fields(files = dr.files.len()))]
 pub async fn process_download_result(
     bot: &Bot,
-    chat_id: ChatId,
+    ctx: &MessageContext,
     mut dr: DownloadResult,
 ) -> Result<()> {
+    let (path, kind) = primary_media(&mut dr).await?;
+    send_media_from_path(bot, ctx, path, kind, dr.info.as_ref()).await
+}
+
+/// Pick the media file to relay from a `DownloadResult`.
+///
+/// Detects media kinds (async) and prefers video over image. The returned
+/// path lives in `dr.tempdir`, so keep `dr` alive while using it.
+///
+/// # Errors
+///
+/// - Returns `Error::NoMediaFound` if no file holds recognizable media.
+pub async fn primary_media(dr: &mut DownloadResult) -> Result<(PathBuf, MediaKind)> {
     debug!(files = dr.files.len(), "Processing download result");
 
     if dr.files.is_empty() {
@@ -194,10 +270,6 @@ pub async fn process_download_result(
 
     let mut media_items = results.into_iter().flatten().collect::<Vec<_>>();
 
-    if media_items.is_empty() {
-        return Err(Error::NoMediaFound);
-    }
-
     // deterministic ordering
     media_items.sort_by_key(|(_, k)| match k {
         MediaKind::Video => 0,
@@ -205,13 +277,9 @@ pub async fn process_download_result(
         MediaKind::Unknown => 2,
     });
 
-    debug!(media_items = media_items.len(), "Sending media to chat");
-
-    if let Some((path, kind)) = media_items.first() {
-        return send_media_from_path(bot, chat_id, path.clone(), *kind).await;
-    }
+    debug!(media_items = media_items.len(), "Detected media items");
 
-    Err(Error::NoMediaFound)
+    media_items.into_iter().next().ok_or(Error::NoMediaFound)
 }
 
 /// Filter function to determine if a file is potentially media based on name/extension.
@@ -241,22 +309,44 @@ fn is_potential_media_file(path: &Path) -> bool {
         .any(|allowed| allowed.eq_ignore_ascii_case(&ext))
 }
 
-async fn run_yt_dlp(
-    base_args: &[&str],
-    cookies_path: Option<&PathBuf>,
-    url: &str,
-) -> Result<DownloadResult> {
+/// Run yt-dlp with the next cookie account of `platform`, if it has any.
+async fn run_yt_dlp(base_args: &[&str], platform: Platform, url: &str) -> Result<DownloadResult> {
     let cookies_path_str;
     let mut args = base_args.to_vec();
-
-    if let Some(path) = cookies_path {
-        cookies_path_str = path.to_string_lossy();
+    args.push("--write-info-json");
+
+    let account = global_accounts().pick(platform, Instant::now());
+    let jar = match &account {
+        Some(account) => {
+            // fail rather than run without cookies: the login error that
+            // follows would blame the account and its cookies
+            let jar = JarCopy::new(&account.path)
+                .await
+                .inspect_err(|e| warn!(%account, "cannot copy cookies: {e}"))?;
+            info!(%account, "using cookie account");
+            Some(jar)
+        }
+        None => None,
+    };
+    if let Some(jar) = &jar {
+        cookies_path_str = jar.path().to_string_lossy().into_owned();
         args.extend(["--cookies", &cookies_path_str]);
     }
     args.push(url);
 
-    debug!(args = ?args, "downloading content");
-    run_command_in_tempdir("yt-dlp", &args).await
+    debug!("downloading content");
+    let result = run_command_in_tempdir("yt-dlp", &args).await;
+    if let Some(jar) = jar
+        && let Err(e) = jar.merge_back().await
+    {
+        warn!("failed to merge updated cookies: {e}");
+    }
+    if let Some(account) = &account {
+        global_accounts().finish(account, result.as_ref().err(), Instant::now());
+    }
+    let mut dr = result?;
+    dr.info = read_info_json(dr.tempdir.path()).await;
+    Ok(dr)
 }
 
 #[cfg(test)]
@@ -270,5 +360,18 @@ mod tests {
         assert!(!is_potential_media_file(Path::new(".DS_Store")));
         assert!(!is_potential_media_file(Path::new("metadata.json")));
         assert!(!is_potential_media_file(Path::new("download.log")));
+        assert!(!is_potential_media_file(Path::new("clip.info.json")));
+    }
+
+    #[test]
+    fn media_info_age_limit() {
+        let info: MediaInfo =
+            serde_json::from_str(r#"{"title":"clip","age_limit":18,"formats":[]}"#)
+                .expect("valid info json");
+        assert_eq!(info.title.as_deref(), Some("clip"));
+        assert!(info.is_age_restricted());
+
+        let info: MediaInfo = serde_json::from_str(r#"{"age_limit":null}"#).expect("valid");
+        assert!(!info.is_age_restricted());
     }
 }
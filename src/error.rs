@@ -5,8 +5,8 @@ pub enum Error {
     #[error("io error: {0}")]
     Io(#[from] tokio::io::Error),
 
-    #[error("yt-dpl failed: {0}")]
-    YTDLPFailed(String),
+    #[error("yt-dpl failed: {stderr}")]
+    YTDLPFailed { code: Option<i32>, stderr: String },
 
     #[error("no media found")]
     NoMediaFound,
@@ -31,14 +31,51 @@ pub enum Error {
 }
 
 impl Error {
+    /// Short, stable label for metrics and reports.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::YTDLPFailed { .. } => "ytdlp",
+            Self::NoMediaFound => "no_media",
+            Self::UnknownMediaKind => "unknown_media_kind",
+            Self::ValidationFailed(_) => "validation",
+            Self::Teloxide(_) => "telegram",
+            Self::Join(_) => "join",
+            Self::EnvNotFound(_) => "env",
+            Self::Other(_) => "other",
+        }
+    }
+
     #[inline]
     pub fn other(text: impl Into<String>) -> Self {
         Self::Other(text.into())
     }
 
     #[inline]
-    pub fn ytdlp_failed(text: impl Into<String>) -> Self {
-        Self::YTDLPFailed(text.into())
+    pub fn ytdlp_failed(code: Option<i32>, stderr: impl Into<String>) -> Self {
+        Self::YTDLPFailed {
+            code,
+            stderr: stderr.into(),
+        }
+    }
+
+    /// Exit code of a failed yt-dlp run (`None` when killed by a signal).
+    #[must_use]
+    pub const fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::YTDLPFailed { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    /// Captured stderr of a failed yt-dlp run.
+    #[must_use]
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            Self::YTDLPFailed { stderr, .. } => Some(stderr),
+            _ => None,
+        }
     }
 
     #[inline]
@@ -1,14 +1,33 @@
 use crate::{
+    context::MessageContext,
     download::{DownloadResult, process_download_result},
     error::Result,
+    metrics::{GaugeGuard, metrics},
 };
 use regex::{Error as RegexError, Regex};
-use std::{pin::Pin, sync::Arc};
-use teloxide::{Bot, types::ChatId};
+use std::{fmt::Display, pin::Pin, sync::Arc};
+use teloxide::Bot;
 use tracing::info;
 
 type DownloadFn = fn(String) -> Pin<Box<dyn Future<Output = Result<DownloadResult>> + Send>>;
 
+/// Short random id tying together the logs and admin report of one job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u32);
+
+impl JobId {
+    #[must_use]
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Handler {
     name: &'static str,
@@ -45,15 +64,40 @@ impl Handler {
             .and_then(|c| c.get(0).map(|m| m.as_str()))
     }
 
-    /// Handle a URL by downloading and sending the media.
+    /// Download a URL without sending anything.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error` if the download fails.
+    pub async fn download(&self, url: &str) -> Result<DownloadResult> {
+        (self.func)(url.to_owned()).await
+    }
+
+    /// Handle a URL by downloading and sending the media into `ctx`.
     ///
     /// # Errors
     ///
     /// Returns `Error` if download or media processing fails.
-    pub async fn handle(&self, bot: &Bot, chat_id: ChatId, url: &str) -> Result<()> {
+    pub async fn handle(&self, bot: &Bot, ctx: &MessageContext, url: &str) -> Result<()> {
         info!(handler = %self.name(), url = %url, "handling url");
-        let dr = (self.func)(url.to_owned()).await?;
-        process_download_result(bot, chat_id, dr).await
+        let metrics = metrics();
+        let _in_flight = GaugeGuard::new(&metrics.jobs_in_flight);
+        metrics.requests.with_label_values(&[self.name]).inc();
+
+        let result = async {
+            let dr = self.download(url).await?;
+            process_download_result(bot, ctx, dr).await
+        }
+        .await;
+
+        match &result {
+            Ok(()) => metrics.successes.with_label_values(&[self.name]).inc(),
+            Err(e) => metrics
+                .failures
+                .with_label_values(&[self.name, e.kind()])
+                .inc(),
+        }
+        result
     }
 }
 
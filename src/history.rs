@@ -0,0 +1,187 @@
+use crate::{
+    error::{Error, Result},
+    settings::write_atomic,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        OnceLock, PoisonError, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use teloxide::types::ChatId;
+use tokio::{fs, sync::Mutex, time::interval};
+use tracing::warn;
+
+static GLOBAL_HISTORY: OnceLock<CommentHistory> = OnceLock::new();
+
+/// Upper bound on remembered comments per chat.
+const MAX_SERVED: usize = 1000;
+/// How often changed history is written to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Comments recently shown in each chat, oldest first, persisted as JSON so
+/// non-repeating selection survives restarts.
+#[derive(Debug)]
+pub struct CommentHistory {
+    path: Option<PathBuf>,
+    chats: RwLock<HashMap<i64, Vec<String>>>,
+    /// Changed since the last write.
+    dirty: AtomicBool,
+    /// Serializes writes so an older snapshot never replaces a newer one.
+    write_lock: Mutex<()>,
+}
+
+impl CommentHistory {
+    /// In-memory history that is never written to disk.
+    #[must_use]
+    pub fn ephemeral() -> Self {
+        Self {
+            path: None,
+            chats: RwLock::default(),
+            dirty: AtomicBool::new(false),
+            write_lock: Mutex::const_new(()),
+        }
+    }
+
+    /// Load history from `path`. A missing file yields empty history.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the file exists but cannot be read.
+    /// - Returns `Error::Other` if the file is not valid JSON.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let chats = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::other(format!("invalid comment history file: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            chats: RwLock::new(chats),
+            dirty: AtomicBool::new(false),
+            write_lock: Mutex::const_new(()),
+        })
+    }
+
+    /// Comments served in `chat`, oldest first.
+    #[must_use]
+    pub fn served(&self, chat: ChatId) -> Vec<String> {
+        self.chats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&chat.0)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Remember that `comment` was shown in `chat`, keeping at most `keep`
+    /// entries. `reset` forgets everything served before.
+    ///
+    /// The file is written by the next [`CommentHistory::flush`].
+    pub fn record(&self, chat: ChatId, comment: &str, reset: bool, keep: usize) {
+        let mut chats = self.chats.write().unwrap_or_else(PoisonError::into_inner);
+        let served = chats.entry(chat.0).or_default();
+        if reset {
+            served.clear();
+        }
+        served.push(comment.to_owned());
+        let excess = served.len().saturating_sub(keep.clamp(1, MAX_SERVED));
+        served.drain(..excess);
+        drop(chats);
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Write the history if it changed since the last write.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing fails; the next flush retries.
+    pub async fn flush(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let result = self.persist().await;
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    async fn persist(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _guard = self.write_lock.lock().await;
+        let snapshot =
+            serde_json::to_string(&*self.chats.read().unwrap_or_else(PoisonError::into_inner))
+                .map_err(|e| Error::other(format!("failed to serialize comment history: {e}")))?;
+        write_atomic(path, &snapshot).await
+    }
+
+    /// Initialize the global history (call once at startup).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Other` when the global is already initialized.
+    pub fn init(self) -> Result<()> {
+        GLOBAL_HISTORY
+            .set(self)
+            .map_err(|_| Error::other("comment history already initialized"))
+    }
+}
+
+/// Write changed comment history every `FLUSH_INTERVAL`.
+pub async fn flush_history() {
+    let mut ticker = interval(FLUSH_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = global_history().flush().await {
+            warn!("failed to save comment history: {e}");
+        }
+    }
+}
+
+/// Get global comment history (initialized by `CommentHistory::init(self)`).
+///
+/// # Panics
+///
+/// Panics if the history has not been initialized.
+#[inline]
+#[must_use]
+pub fn global_history() -> &'static CommentHistory {
+    GLOBAL_HISTORY
+        .get()
+        .expect("comment history not initialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_trims_and_reloads() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("history.json");
+
+        let history = CommentHistory::load(&path)
+            .await
+            .expect("missing file is fine");
+        let chat = ChatId(-100);
+        for comment in ["a", "b", "c"] {
+            history.record(chat, comment, false, 2);
+        }
+        assert_eq!(history.served(chat), ["b", "c"]);
+        history.record(chat, "d", true, 2);
+        assert_eq!(history.served(chat), ["d"]);
+
+        history.flush().await.expect("write history");
+        assert!(!history.dirty.load(Ordering::Acquire));
+        let reloaded = CommentHistory::load(&path).await.expect("reload");
+        assert_eq!(reloaded.served(chat), ["d"]);
+    }
+}
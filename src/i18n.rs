@@ -0,0 +1,280 @@
+use crate::error::{Error, Result};
+use fluent_bundle::{FluentArgs, FluentResource, concurrent::FluentBundle};
+use std::{collections::BTreeMap, path::Path, sync::OnceLock};
+use tokio::fs;
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+static GLOBAL_CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Language used when neither the chat nor the sender picks a supported one.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Attribute holding the polite variant of a message (clean mode).
+const CLEAN_ATTRIBUTE: &str = "clean";
+
+/// Message files compiled into the binary.
+const BUILTIN: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("lv", include_str!("../locales/lv.ftl")),
+];
+
+/// Look up a message in the global catalogue.
+///
+/// ```ignore
+/// tr!(lang, "failed-fetch")
+/// tr!(lang, "comment-added", id = 12)
+/// ```
+#[macro_export]
+macro_rules! tr {
+    ($lang:expr, $key:expr) => {
+        $crate::i18n::global_catalog().get($lang, $key, None)
+    };
+    ($lang:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = ::fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::global_catalog().get($lang, $key, Some(&args))
+    }};
+}
+
+/// User-facing messages per language, from Fluent (`.ftl`) files.
+pub struct Catalog {
+    bundles: BTreeMap<String, FluentBundle<FluentResource>>,
+    default: String,
+}
+
+impl std::fmt::Debug for Catalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Catalog")
+            .field("languages", &self.bundles.keys().collect::<Vec<_>>())
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+impl Catalog {
+    /// Catalogue with only the built-in languages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a built-in message file is invalid.
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut catalog = Self {
+            bundles: BTreeMap::new(),
+            default: DEFAULT_LANGUAGE.into(),
+        };
+        for (lang, source) in BUILTIN {
+            catalog
+                .add(lang, source)
+                .expect("built-in message files are valid");
+        }
+        catalog
+    }
+
+    /// Built-in languages plus every `<lang>.ftl` file in `dir`, which add
+    /// new languages or override built-in messages. A missing directory is
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the directory or a file cannot be read.
+    /// - Returns `Error::ValidationFailed` for invalid Fluent files, or when
+    ///   `default` is not an available language.
+    pub async fn load(dir: &Path, default: &str) -> Result<Self> {
+        let mut catalog = Self::builtin();
+        match fs::read_dir(dir).await {
+            Ok(mut entries) => {
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    if path.extension().is_none_or(|ext| ext != "ftl") {
+                        continue;
+                    }
+                    let Some(lang) = path.file_stem().and_then(|s| s.to_str()) else {
+                        continue;
+                    };
+                    let source = fs::read_to_string(&path).await?;
+                    catalog.add(lang, &source)?;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if !catalog.supports(default) {
+            return Err(Error::validation_falied(format!(
+                "default language `{default}` has no message file"
+            )));
+        }
+        catalog.default = default.to_owned();
+        Ok(catalog)
+    }
+
+    fn add(&mut self, lang: &str, source: &str) -> Result<()> {
+        let id = lang
+            .parse::<LanguageIdentifier>()
+            .map_err(|e| Error::validation_falied(format!("invalid language `{lang}`: {e}")))?;
+        let resource = FluentResource::try_new(source.to_owned()).map_err(|(_, errors)| {
+            Error::validation_falied(format!("invalid messages for `{lang}`: {errors:?}"))
+        })?;
+
+        let bundle = self.bundles.entry(lang.to_owned()).or_insert_with(|| {
+            let mut bundle = FluentBundle::new_concurrent(vec![id]);
+            // Telegram shows the bidi isolation marks as garbage
+            bundle.set_use_isolating(false);
+            bundle
+        });
+        bundle.add_resource_overriding(resource);
+        Ok(())
+    }
+
+    /// Available language codes.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.bundles.keys().map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn supports(&self, lang: &str) -> bool {
+        self.bundles.contains_key(lang)
+    }
+
+    /// Language for a chat: its setting, else the sender's Telegram
+    /// `language_code` (e.g. `en-US` -> `en`), else the default.
+    #[must_use]
+    pub fn resolve(&self, chat: Option<&str>, user: Option<&str>) -> String {
+        let user = user.map(|code| code.split(['-', '_']).next().unwrap_or(code));
+        [chat, user]
+            .into_iter()
+            .flatten()
+            .find(|lang| self.supports(lang))
+            .unwrap_or(&self.default)
+            .to_owned()
+    }
+
+    /// Format message `key` in `lang`, falling back to the default language
+    /// and finally to the key itself.
+    #[must_use]
+    pub fn get(&self, lang: &str, key: &str, args: Option<&FluentArgs>) -> String {
+        self.format(lang, key, None, args)
+    }
+
+    /// Like [`Catalog::get`], but prefers the message's polite `.clean`
+    /// attribute when it has one.
+    #[must_use]
+    pub fn get_clean(&self, lang: &str, key: &str, args: Option<&FluentArgs>) -> String {
+        self.format(lang, key, Some(CLEAN_ATTRIBUTE), args)
+    }
+
+    fn format(
+        &self,
+        lang: &str,
+        key: &str,
+        attribute: Option<&str>,
+        args: Option<&FluentArgs>,
+    ) -> String {
+        for lang in [lang, self.default.as_str()] {
+            let Some(bundle) = self.bundles.get(lang) else {
+                continue;
+            };
+            let Some(message) = bundle.get_message(key) else {
+                continue;
+            };
+            let Some(pattern) = attribute
+                .and_then(|name| message.get_attribute(name))
+                .map(|attr| attr.value())
+                .or_else(|| message.value())
+            else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            if !errors.is_empty() {
+                warn!(lang, key, ?errors, "failed to format message");
+            }
+            return text.into_owned();
+        }
+        warn!(lang, key, "missing message");
+        key.to_owned()
+    }
+
+    /// Initialize the global catalogue (call once at startup).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Other` when the global is already initialized.
+    pub fn init(self) -> Result<()> {
+        GLOBAL_CATALOG
+            .set(self)
+            .map_err(|_| Error::other("message catalogue already initialized"))
+    }
+}
+
+/// Get the global message catalogue, or the built-in one if
+/// `Catalog::init(self)` was never called.
+#[inline]
+#[must_use]
+pub fn global_catalog() -> &'static Catalog {
+    GLOBAL_CATALOG.get_or_init(Catalog::builtin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_languages_have_the_same_keys() {
+        let keys = |source: &str| {
+            let mut keys = source
+                .lines()
+                .filter_map(|line| line.split_once(" ="))
+                .map(|(key, _)| key.to_owned())
+                .filter(|key| !key.starts_with([' ', '#']))
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+        let (_, english) = BUILTIN[0];
+        for (lang, source) in BUILTIN {
+            assert_eq!(keys(source), keys(english), "{lang}");
+        }
+    }
+
+    #[test]
+    fn formats_with_fallbacks() {
+        let catalog = Catalog::builtin();
+        let mut args = FluentArgs::new();
+        args.set("id", 12);
+        assert_eq!(
+            catalog.get("lv", "comment-added", Some(&args)),
+            "Pievienots komentārs #12."
+        );
+        assert_eq!(
+            catalog.get("de", "comment-added", Some(&args)),
+            "Added comment #12."
+        );
+        assert_eq!(catalog.get("en", "no-such-key", None), "no-such-key");
+    }
+
+    #[test]
+    fn clean_variants() {
+        let catalog = Catalog::builtin();
+        for lang in catalog.languages() {
+            let clean = catalog.get_clean(lang, "failed-fetch", None);
+            assert_ne!(clean, catalog.get(lang, "failed-fetch", None), "{lang}");
+            assert!(!clean.contains("fok"), "{lang}");
+        }
+        // messages without a polite variant are used as they are
+        assert_eq!(
+            catalog.get_clean("en", "no-media", None),
+            catalog.get("en", "no-media", None)
+        );
+    }
+
+    #[test]
+    fn resolves_language() {
+        let catalog = Catalog::builtin();
+        assert_eq!(catalog.resolve(Some("lv"), Some("en")), "lv");
+        assert_eq!(catalog.resolve(None, Some("lv-LV")), "lv");
+        assert_eq!(catalog.resolve(Some("xx"), Some("de")), DEFAULT_LANGUAGE);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    caption::{Caption, MEDIA_CAPTION_LIMIT},
+    comments::global_comments,
+    config::global_config,
+    download::primary_media,
+    error::{Error, Result},
+    handler::Handler,
+    i18n::global_catalog,
+    ratelimit::{Decision, RateLimiter},
+    shutdown::Jobs,
+    template::CaptionContext,
+    tr,
+    utils::MediaKind,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+use teloxide::{
+    prelude::*,
+    types::{
+        FileId, InlineQueryResult, InlineQueryResultArticle, InlineQueryResultCachedPhoto,
+        InlineQueryResultCachedVideo, InputFile, InputMessageContent, InputMessageContentText,
+    },
+};
+use tracing::{Instrument, error, info, info_span, warn};
+
+/// Upper bound on remembered URLs; the oldest entries are evicted first.
+const MAX_ENTRIES: usize = 1000;
+
+/// Media uploaded to the cache chat, reusable through its `file_id`.
+#[derive(Debug, Clone)]
+pub struct CachedMedia {
+    pub file_id: FileId,
+    pub kind: MediaKind,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Pending,
+    Ready(CachedMedia),
+}
+
+/// What [`FileIdCache::lookup_or_start`] found for a URL.
+#[derive(Debug, Clone)]
+pub enum Lookup {
+    /// Media is cached and can be answered right away.
+    Ready(CachedMedia),
+    /// A download for this URL is already running.
+    Pending,
+    /// Nothing known yet; the caller should start the download.
+    Started,
+}
+
+/// URL -> `file_id` cache for inline answers.
+#[derive(Debug, Default)]
+pub struct FileIdCache {
+    entries: Mutex<HashMap<String, (Entry, Instant)>>,
+}
+
+impl FileIdCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up `url`, marking it as pending when it is unknown.
+    pub fn lookup_or_start(&self, url: &str) -> Lookup {
+        let mut entries = self.lock();
+        match entries.get(url) {
+            Some((Entry::Ready(media), _)) => Lookup::Ready(media.clone()),
+            Some((Entry::Pending, _)) => Lookup::Pending,
+            None => {
+                Self::evict(&mut entries);
+                entries.insert(url.to_owned(), (Entry::Pending, Instant::now()));
+                Lookup::Started
+            }
+        }
+    }
+
+    /// Store the uploaded media for `url`, or forget `url` if the job failed.
+    pub fn finish(&self, url: &str, media: Option<CachedMedia>) {
+        let mut entries = self.lock();
+        match media {
+            Some(media) => {
+                entries.insert(url.to_owned(), (Entry::Ready(media), Instant::now()));
+            }
+            None => {
+                entries.remove(url);
+            }
+        }
+    }
+
+    fn evict(entries: &mut HashMap<String, (Entry, Instant)>) {
+        while entries.len() >= MAX_ENTRIES {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (_, at))| *at)
+                .map(|(url, _)| url.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (Entry, Instant)>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Answer an inline query (`@bot <link>`).
+///
+/// Cached links are answered with the stored `file_id`. Otherwise the
+/// download is started in the background and a placeholder is shown until the
+/// user repeats the query.
+///
+/// # Errors
+///
+/// Returns a Teloxide error if answering the query fails.
+pub async fn answer_inline_query(
+    bot: &Bot,
+    query: &InlineQuery,
+    handlers: &[Handler],
+    cache: &Arc<FileIdCache>,
+    limiter: &RateLimiter,
+    jobs: &Jobs,
+) -> ResponseResult<()> {
+    let found = handlers
+        .iter()
+        .find_map(|handler| handler.try_extract(&query.query).map(|url| (handler, url)));
+
+    let (Some(cache_chat), Some((handler, url))) = (global_config().inline_cache_chat, found)
+    else {
+        bot.answer_inline_query(query.id.clone(), []).await?;
+        return Ok(());
+    };
+
+    let lang = global_catalog().resolve(None, query.from.language_code.as_deref());
+    let result = match cache.lookup_or_start(url) {
+        Lookup::Ready(media) => {
+            let caption = CaptionContext {
+                platform: Some(handler.name().into()),
+                kind: Some(media.kind),
+                lang: Some(lang.clone()),
+                source: Some(url.to_owned()),
+                ..CaptionContext::from_user(&query.from)
+            };
+            cached_result(&media, &caption, &lang)
+        }
+        Lookup::Pending => pending_result(&lang),
+        Lookup::Started => {
+            let chat_id = ChatId::from(query.from.id);
+            if let Decision::Throttle { .. } =
+                limiter.check(chat_id, Some(query.from.id), handler.name())
+            {
+                warn!(user_id = %query.from.id, "inline query rate limited");
+                cache.finish(url, None);
+                bot.answer_inline_query(query.id.clone(), []).await?;
+                return Ok(());
+            }
+
+            let (bot, handler, cache, task_jobs) = (
+                bot.clone(),
+                handler.clone(),
+                Arc::clone(cache),
+                jobs.clone(),
+            );
+            let url = url.to_owned();
+            let span = info_span!("inline", handler = handler.name(), url);
+            // tracked so the shutdown waits for the upload
+            jobs.spawn(
+                async move {
+                    let media = task_jobs
+                        .run(upload_to_cache(&bot, cache_chat, &handler, &url))
+                        .await;
+                    match media {
+                        Some(Ok(media)) => cache.finish(&url, Some(media)),
+                        Some(Err(err)) => {
+                            error!(%err, "inline download failed");
+                            cache.finish(&url, None);
+                        }
+                        None => cache.finish(&url, None),
+                    }
+                }
+                .instrument(span),
+            );
+            pending_result(&lang)
+        }
+    };
+
+    bot.answer_inline_query(query.id.clone(), [result])
+        .cache_time(0)
+        .is_personal(true)
+        .await?;
+    Ok(())
+}
+
+/// Download `url` and upload it to the cache chat to obtain a `file_id`.
+async fn upload_to_cache(
+    bot: &Bot,
+    cache_chat: ChatId,
+    handler: &Handler,
+    url: &str,
+) -> Result<CachedMedia> {
+    let mut dr = handler.download(url).await?;
+    let (path, kind) = primary_media(&mut dr).await?;
+    let input = InputFile::file(path);
+
+    let file_id = match kind {
+        MediaKind::Video => bot
+            .send_video(cache_chat, input)
+            .await?
+            .video()
+            .map(|video| video.file.id.clone()),
+        MediaKind::Image => bot
+            .send_photo(cache_chat, input)
+            .await?
+            .photo()
+            .and_then(|sizes| sizes.last())
+            .map(|photo| photo.file.id.clone()),
+        MediaKind::Unknown => return Err(Error::UnknownMediaKind),
+    };
+
+    let file_id = file_id.ok_or(Error::NoMediaFound)?;
+    info!(%kind, "uploaded media to cache chat");
+    Ok(CachedMedia { file_id, kind })
+}
+
+fn cached_result(media: &CachedMedia, caption: &CaptionContext, lang: &str) -> InlineQueryResult {
+    let comment = global_comments().build_caption(caption);
+    let text = Caption::compose(caption, comment, None).render_configured(MEDIA_CAPTION_LIMIT);
+    let parse_mode = global_config().caption.format.parse_mode();
+    match media.kind {
+        MediaKind::Image => {
+            let mut result =
+                InlineQueryResultCachedPhoto::new("media", media.file_id.clone()).caption(text);
+            if let Some(mode) = parse_mode {
+                result = result.parse_mode(mode);
+            }
+            result.into()
+        }
+        MediaKind::Video | MediaKind::Unknown => {
+            let title = tr!(lang, "inline-send-video");
+            let mut result =
+                InlineQueryResultCachedVideo::new("media", media.file_id.clone(), title)
+                    .caption(text);
+            if let Some(mode) = parse_mode {
+                result = result.parse_mode(mode);
+            }
+            result.into()
+        }
+    }
+}
+
+fn pending_result(lang: &str) -> InlineQueryResult {
+    let text = tr!(lang, "inline-pending");
+    InlineQueryResultArticle::new(
+        "pending",
+        tr!(lang, "inline-pending-title"),
+        InputMessageContent::Text(InputMessageContentText::new(text.clone())),
+    )
+    .description(text)
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_transitions() {
+        let cache = FileIdCache::new();
+        let url = "https://vm.tiktok.com/abc";
+
+        assert!(matches!(cache.lookup_or_start(url), Lookup::Started));
+        assert!(matches!(cache.lookup_or_start(url), Lookup::Pending));
+
+        cache.finish(
+            url,
+            Some(CachedMedia {
+                file_id: FileId("id".into()),
+                kind: MediaKind::Video,
+            }),
+        );
+        assert!(matches!(cache.lookup_or_start(url), Lookup::Ready(_)));
+    }
+
+    #[test]
+    fn failed_job_can_be_retried() {
+        let cache = FileIdCache::new();
+        let url = "https://vm.tiktok.com/abc";
+
+        assert!(matches!(cache.lookup_or_start(url), Lookup::Started));
+        cache.finish(url, None);
+        assert!(matches!(cache.lookup_or_start(url), Lookup::Started));
+    }
+}
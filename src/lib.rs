@@ -1,8 +1,27 @@
+pub mod accounts;
+pub mod caption;
 pub mod commands;
 pub mod comments;
 pub mod config;
+pub mod context;
+pub mod cookies;
 pub mod download;
 pub mod error;
 pub mod handler;
+pub mod history;
+pub mod i18n;
+pub mod inline;
+pub mod markov;
+pub mod metrics;
+pub mod monitor;
+pub mod packs;
+pub mod ratelimit;
+pub mod reload;
+pub mod report;
+pub mod settings;
+pub mod shutdown;
+pub mod store;
+pub mod targets;
 pub mod telemetry;
+pub mod template;
 pub mod utils;
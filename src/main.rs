@@ -1,75 +1,307 @@
 use dotenv::dotenv;
 use std::sync::Arc;
-use teloxide::{prelude::*, respond, utils::command::BotCommands};
+use teloxide::{
+    prelude::*,
+    respond,
+    update_listeners::{polling_default, webhooks},
+    utils::command::BotCommands,
+};
 use tg_relay_rs::{
-    commands::{Command, answer},
+    commands::{Command, answer, answer_callback},
     comments::Comments,
-    config::{Config, FAILED_FETCH_MEDIA_MESSAGE, global_config},
-    handler::{Handler, create_handlers},
+    config::{Config, LogConfig, global_config},
+    context::MessageContext,
+    handler::{Handler, JobId, create_handlers},
+    history::{CommentHistory, flush_history, global_history},
+    i18n::Catalog,
+    inline::{FileIdCache, answer_inline_query},
+    metrics,
+    monitor::{global_monitor, watch_cookies},
+    ratelimit::{Decision, RateLimiter},
+    reload::watch_comments,
+    report::{Failure, global_reporter},
+    settings::{Settings, global_settings},
+    shutdown::{Jobs, graceful_shutdown},
+    store::{CommentStore, global_store},
+    targets::CurseTargets,
     telemetry::setup_logger,
+    utils::{attribution, wants_spoiler},
 };
-use tracing::{error, info, warn};
+use tracing::{Instrument, Span, error, field::Empty, info, info_span, warn};
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     dotenv().ok();
     color_eyre::install().expect("color-eyre install");
-    setup_logger();
-
-    Comments::load_from_file("comments.txt")
-        .await
-        .unwrap_or_else(|e| {
-            warn!("failed to load comments.txt: {e}; using dummy comments");
-            Comments::dummy()
-        })
-        .init()?;
+    let _logger = setup_logger(&LogConfig::from_env());
 
     Config::from_env().init()?;
 
+    load_state().await?;
+
     let bot = Bot::from_env();
     let bot_name: Arc<str> = bot.get_me().await?.username().into();
 
     info!(name = %bot_name, "bot starting");
 
+    if let Some(address) = global_config().metrics_address {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(address).await {
+                error!(%address, "metrics server failed: {e}");
+            }
+        });
+    }
+
+    tokio::spawn(watch_comments(
+        bot.clone(),
+        global_config().comments.clone(),
+    ));
+
+    tokio::spawn(watch_cookies(bot.clone()));
+    tokio::spawn(flush_history());
+
     let handlers = create_handlers();
+    let limiter = Arc::new(RateLimiter::new(&global_config().rate_limit));
+    let jobs = Jobs::new();
+    let inline_cache = Arc::new(FileIdCache::new());
 
-    teloxide::repl(bot.clone(), move |bot: Bot, msg: Message| {
-        let handlers = Arc::clone(&handlers);
-        let bot_name = Arc::clone(&bot_name);
-        async move {
-            process_cmd(&bot, &msg, &bot_name).await;
-            process_message(&bot, &msg, &handlers).await;
-            respond(())
+    let message_handler = {
+        let (handlers, limiter, jobs) = (Arc::clone(&handlers), Arc::clone(&limiter), jobs.clone());
+        move |bot: Bot, msg: Message| {
+            let handlers = Arc::clone(&handlers);
+            let bot_name = Arc::clone(&bot_name);
+            let limiter = Arc::clone(&limiter);
+            let jobs = jobs.clone();
+            async move {
+                process_cmd(&bot, &msg, &bot_name).await;
+                process_message(&bot, &msg, &handlers, &limiter, &jobs).await;
+                respond(())
+            }
         }
-    })
-    .await;
+    };
+
+    let inline_handler = {
+        let jobs = jobs.clone();
+        move |bot: Bot, query: InlineQuery| {
+            let handlers = Arc::clone(&handlers);
+            let limiter = Arc::clone(&limiter);
+            let inline_cache = Arc::clone(&inline_cache);
+            let jobs = jobs.clone();
+            async move {
+                if let Err(e) =
+                    answer_inline_query(&bot, &query, &handlers, &inline_cache, &limiter, &jobs)
+                        .await
+                {
+                    error!(%e, "failed to answer inline query");
+                }
+                respond(())
+            }
+        }
+    };
+
+    let callback_handler = |bot: Bot, query: CallbackQuery| async move {
+        if let Err(e) = answer_callback(&bot, &query).await {
+            error!(%e, "failed to answer callback query");
+        }
+        respond(())
+    };
+
+    let tree = dptree::entry()
+        .branch(Update::filter_message().endpoint(message_handler))
+        .branch(Update::filter_inline_query().endpoint(inline_handler))
+        .branch(Update::filter_callback_query().endpoint(callback_handler));
+    let mut dispatcher = Dispatcher::builder(bot.clone(), tree)
+        .default_handler(|_| async {})
+        .build();
+    let shutdown = tokio::spawn(graceful_shutdown(
+        dispatcher.shutdown_token(),
+        jobs,
+        global_config().shutdown_grace,
+    ));
+
+    let error_handler = LoggingErrorHandler::with_custom_text("An error from the update listener");
+    if let Some(webhook) = &global_config().webhook {
+        let mut options = webhooks::Options::new(webhook.address, webhook.url.clone());
+        if let Some(token) = &webhook.secret_token {
+            options = options.secret_token(token.clone());
+        }
+        info!(address = %webhook.address, url = %webhook.url, "using webhook");
+        let listener = webhooks::axum(bot, options).await?;
+        dispatcher
+            .dispatch_with_listener(listener, error_handler)
+            .await;
+    } else {
+        info!("using long polling");
+        let listener = polling_default(bot).await;
+        dispatcher
+            .dispatch_with_listener(listener, error_handler)
+            .await;
+    }
+
+    // the dispatcher only stops on shutdown; wait for the spawned tasks
+    let _ = shutdown.await;
+    if let Err(e) = global_history().flush().await {
+        warn!("failed to save comment history: {e}");
+    }
+    info!("bot stopped");
+    Ok(())
+}
+
+/// Load comments and the persisted per-chat state, falling back to defaults.
+async fn load_state() -> color_eyre::Result<()> {
+    let config = global_config();
+    Catalog::load(&config.locales_dir, &config.language)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(dir = %config.locales_dir.display(), "failed to load messages: {e}; using built-in messages");
+            Catalog::builtin()
+        })
+        .init()?;
+
+    let store_path = global_config().data_dir.join("custom_comments.json");
+    CommentStore::load(&store_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(path = %store_path.display(), "failed to load comment store: {e}; starting empty");
+            CommentStore::ephemeral()
+        })
+        .init()?;
+
+    let comments = Comments::load(&global_config().comments, global_store().comments())
+        .await
+        .unwrap_or_else(|e| {
+            warn!("failed to load comments: {e}; using dummy comments");
+            Comments::dummy()
+        });
+    for problem in comments.problems() {
+        warn!("skipped invalid comments: {problem}");
+    }
+    comments.init()?;
+
+    let settings_path = global_config().data_dir.join("settings.json");
+    Settings::load(&settings_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(path = %settings_path.display(), "failed to load settings: {e}; starting empty");
+            Settings::ephemeral()
+        })
+        .init()?;
+
+    let history_path = global_config().data_dir.join("comment_history.json");
+    CommentHistory::load(&history_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(path = %history_path.display(), "failed to load comment history: {e}; starting empty");
+            CommentHistory::ephemeral()
+        })
+        .init()?;
+
+    let targets_path = global_config().data_dir.join("curse_optout.json");
+    CurseTargets::load(&targets_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(path = %targets_path.display(), "failed to load curse opt-outs: {e}; starting empty");
+            CurseTargets::ephemeral()
+        })
+        .init()?;
 
     Ok(())
 }
 
-async fn process_message(bot: &Bot, msg: &Message, handlers: &[Handler]) {
+async fn process_message(
+    bot: &Bot,
+    msg: &Message,
+    handlers: &[Handler],
+    limiter: &RateLimiter,
+    jobs: &Jobs,
+) {
     let Some(text) = msg.text() else {
         return;
     };
 
-    for handler in handlers {
-        if let Some(url) = handler.try_extract(text) {
-            if let Err(err) = handler.handle(bot, msg.chat.id, url).await {
-                error!(%err, "handler failed");
-                let _ = bot
-                    .send_message(msg.chat.id, FAILED_FETCH_MEDIA_MESSAGE)
-                    .await;
-                if let Some(chat_id) = global_config().chat_id {
-                    let _ = bot.send_message(chat_id, err.to_string()).await;
-                }
+    let job_id = JobId::generate();
+    let span = info_span!(
+        "message",
+        %job_id,
+        chat_id = msg.chat.id.0,
+        user_id = Empty,
+        message_id = msg.id.0,
+        handler = Empty,
+    );
+    let user_id = msg.from.as_ref().map(|user| user.id);
+    if let Some(id) = user_id {
+        span.record("user_id", id.0);
+    }
+
+    async {
+        let Some((handler, url)) = handlers
+            .iter()
+            .find_map(|handler| handler.try_extract(text).map(|url| (handler, url)))
+        else {
+            return;
+        };
+        Span::current().record("handler", handler.name());
+
+        let mut ctx = MessageContext::from_message(msg);
+        ctx.spoiler = wants_spoiler(msg, url);
+        ctx.caption.platform = Some(handler.name().into());
+        ctx.caption.source = Some(url.to_owned());
+
+        if let Decision::Throttle { notify } = limiter.check(msg.chat.id, user_id, handler.name()) {
+            warn!("rate limited");
+            if notify {
+                let _ = ctx.send_message(bot, ctx.message("rate-limited")).await;
             }
             return;
         }
+
+        let replace = global_settings().get(msg.chat.id).replace;
+        let mut media = ctx.clone();
+        if replace {
+            // the source is deleted once the media is sent, so the media does
+            // not reply to it; status and error messages still do
+            media.reply_to = None;
+            media.attribution = Some(attribution(msg, url, &ctx.lang));
+        }
+
+        let Some(result) = jobs.run(handler.handle(bot, &media, url)).await else {
+            warn!(url, "job cancelled by shutdown");
+            let _ = ctx.send_message(bot, ctx.message("job-cancelled")).await;
+            return;
+        };
+
+        if replace
+            && result.is_ok()
+            && let Err(e) = bot.delete_message(msg.chat.id, msg.id).await
+        {
+            warn!("cannot delete original message, keeping it: {e}");
+        }
+
+        if let Err(err) = result {
+            error!(%err, "handler failed");
+            let _ = ctx.send_message(bot, ctx.message("failed-fetch")).await;
+            global_monitor()
+                .observe_failure(bot, handler.name(), &err)
+                .await;
+            if let Some(chat_id) = global_config().chat_id {
+                let failure = Failure {
+                    job_id,
+                    handler: handler.name(),
+                    url,
+                    msg,
+                    error: &err,
+                };
+                global_reporter().report(bot, chat_id, &failure).await;
+            }
+        }
     }
+    .instrument(span)
+    .await;
 }
 
 async fn process_cmd(bot: &Bot, msg: &Message, bot_name: &str) {
-    if let Some(text) = msg.text()
+    // commands also work as captions, e.g. `/cookies` on a file
+    if let Some(text) = msg.text().or_else(|| msg.caption())
         && let Ok(cmd) = Command::parse(text, bot_name)
         && let Err(e) = answer(bot, msg, cmd).await
     {
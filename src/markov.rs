@@ -0,0 +1,117 @@
+use rand::{Rng, seq::IndexedRandom};
+use std::collections::{HashMap, HashSet};
+
+/// Word-level Markov chain trained on comment lines.
+///
+/// Every run of `order` words maps to the words seen after it (`None` marks
+/// the end of a line), so generated text recombines the training lines.
+#[derive(Debug, Clone)]
+pub struct Markov {
+    order: usize,
+    next: HashMap<Vec<String>, Vec<Option<String>>>,
+    starts: Vec<Vec<String>>,
+    /// Normalized training lines, to reject verbatim copies.
+    lines: HashSet<String>,
+}
+
+impl Markov {
+    /// Build a model of the given `order` (words of context, at least 1).
+    /// Lines shorter than `order` words only count for the verbatim check.
+    pub fn new<'a>(order: usize, lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let order = order.max(1);
+        let mut model = Self {
+            order,
+            next: HashMap::new(),
+            starts: Vec::new(),
+            lines: HashSet::new(),
+        };
+        for line in lines {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            model.lines.insert(words.join(" "));
+            if words.len() < order {
+                continue;
+            }
+            model.starts.push(to_owned(&words[..order]));
+            for (i, window) in words.windows(order).enumerate() {
+                let follower = words.get(i + order).map(|&w| w.to_owned());
+                model
+                    .next
+                    .entry(to_owned(window))
+                    .or_default()
+                    .push(follower);
+            }
+        }
+        model
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Walk the chain once. Returns `None` when the result is a verbatim
+    /// training line or longer than `max_words`.
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R, max_words: usize) -> Option<String> {
+        let mut words = self.starts.choose(rng)?.clone();
+        loop {
+            let state = &words[words.len() - self.order..];
+            match self.next.get(state)?.choose(rng)? {
+                Some(word) => words.push(word.clone()),
+                None => break,
+            }
+            if words.len() > max_words {
+                return None;
+            }
+        }
+        let text = words.join(" ");
+        (!self.lines.contains(&text)).then_some(text)
+    }
+}
+
+fn to_owned(words: &[&str]) -> Vec<String> {
+    words.iter().map(|&w| w.to_owned()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const LINES: &[&str] = &[
+        "The car is a piece of junk today",
+        "The car is faster than the data says",
+        "Tell him the data says box now",
+        "Tell him to stop looking at the birds",
+    ];
+
+    fn generate_all(model: &Markov, seed: u64) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..50)
+            .filter_map(|_| model.generate(&mut rng, 20))
+            .collect()
+    }
+
+    #[test]
+    fn generates_new_lines_deterministically() {
+        let model = Markov::new(2, LINES.iter().copied());
+        let generated = generate_all(&model, 7);
+        assert!(!generated.is_empty());
+        assert!(generated.iter().all(|line| !LINES.contains(&line.as_str())));
+        assert_eq!(generated, generate_all(&model, 7));
+    }
+
+    #[test]
+    fn rejects_copies_and_long_output() {
+        let single = Markov::new(2, ["only one line here"]);
+        assert!(generate_all(&single, 1).is_empty());
+
+        let model = Markov::new(1, LINES.iter().copied());
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            if let Some(line) = model.generate(&mut rng, 8) {
+                assert!(line.split_whitespace().count() <= 8, "{line}");
+            }
+        }
+        assert!(Markov::new(2, ["short"]).is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use axum::{Router, http::StatusCode, routing::get};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+    exponential_buckets,
+};
+use std::{net::SocketAddr, sync::LazyLock};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Prometheus collectors exposed on `/metrics`.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    /// Jobs started, by handler.
+    pub requests: IntCounterVec,
+    /// Jobs that delivered media, by handler.
+    pub successes: IntCounterVec,
+    /// Jobs that failed, by handler and `Error::kind`.
+    pub failures: IntCounterVec,
+    /// Wall time of yt-dlp runs.
+    pub download_duration: Histogram,
+    /// Wall time of standalone ffmpeg runs.
+    pub transcode_duration: Histogram,
+    /// Wall time of Telegram media uploads.
+    pub upload_duration: Histogram,
+    /// Size of uploaded media files.
+    pub file_size: Histogram,
+    /// Jobs currently inside `Handler::handle`.
+    pub jobs_in_flight: IntGauge,
+    /// yt-dlp processes currently running.
+    pub ytdlp_in_flight: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("tg_relay".into()), None).expect("valid registry prefix");
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter =
+                IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter opts");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("unique counter");
+            counter
+        };
+        let histogram = |name: &str, help: &str, buckets: Vec<f64>| {
+            let histogram = Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets))
+                .expect("valid histogram opts");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("unique histogram");
+            histogram
+        };
+        let gauge = |name: &str, help: &str| {
+            let gauge = IntGauge::new(name, help).expect("valid gauge opts");
+            registry
+                .register(Box::new(gauge.clone()))
+                .expect("unique gauge");
+            gauge
+        };
+
+        let seconds = exponential_buckets(0.25, 2.0, 12).expect("valid buckets");
+        let bytes = exponential_buckets(64.0 * 1024.0, 2.0, 12).expect("valid buckets");
+
+        Self {
+            requests: counter("requests_total", "Jobs started", &["handler"]),
+            successes: counter("successes_total", "Jobs that delivered media", &["handler"]),
+            failures: counter("failures_total", "Jobs that failed", &["handler", "kind"]),
+            download_duration: histogram(
+                "download_duration_seconds",
+                "yt-dlp run time",
+                seconds.clone(),
+            ),
+            transcode_duration: histogram(
+                "transcode_duration_seconds",
+                "ffmpeg run time",
+                seconds.clone(),
+            ),
+            upload_duration: histogram("upload_duration_seconds", "Telegram upload time", seconds),
+            file_size: histogram("file_size_bytes", "Uploaded media size", bytes),
+            jobs_in_flight: gauge("jobs_in_flight", "Jobs currently being handled"),
+            ytdlp_in_flight: gauge("ytdlp_in_flight", "Running yt-dlp processes"),
+            registry,
+        }
+    }
+
+    /// Render all collectors in the Prometheus text format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_else(|e| {
+                error!("failed to encode metrics: {e}");
+                String::new()
+            })
+    }
+}
+
+/// Get the process-wide metrics.
+#[inline]
+#[must_use]
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Increments a gauge and decrements it again when dropped, so cancelled
+/// jobs are accounted for too.
+#[derive(Debug)]
+pub struct GaugeGuard(IntGauge);
+
+impl GaugeGuard {
+    #[must_use]
+    pub fn new(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Serve `/metrics` on `address` until the process exits.
+///
+/// # Errors
+///
+/// Returns an error if binding or serving fails.
+pub async fn serve(address: SocketAddr) -> std::io::Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                StatusCode::OK,
+                [("content-type", TextEncoder::new().format_type().to_owned())],
+                metrics().render(),
+            )
+        }),
+    );
+    let listener = TcpListener::bind(address).await?;
+    info!(%address, "serving metrics");
+    axum::serve(listener, app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gauge_guard_restores_value() {
+        let gauge = IntGauge::new("test_gauge", "test").expect("valid gauge");
+        {
+            let _guard = GaugeGuard::new(&gauge);
+            assert_eq!(gauge.get(), 1);
+        }
+        assert_eq!(gauge.get(), 0);
+    }
+
+    #[test]
+    fn render_contains_prefixed_names() {
+        metrics().requests.with_label_values(&["test"]).inc();
+        let text = metrics().render();
+        assert!(text.contains("tg_relay_requests_total{handler=\"test\"}"));
+        assert!(text.contains("tg_relay_ytdlp_in_flight"));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    accounts::Account,
+    config::global_config,
+    cookies::{self, Expiry, Platform},
+    error::Error,
+    store::format_date,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use teloxide::prelude::*;
+use tokio::{fs, time::interval};
+use tracing::{info, warn};
+
+static GLOBAL_MONITOR: OnceLock<CookieMonitor> = OnceLock::new();
+
+/// yt-dlp errors (lowercase) that mean the platform wants a logged-in session.
+const LOGIN_ERRORS: &[&str] = &[
+    "login required",
+    "log in to",
+    "sign in to confirm",
+    "use --cookies",
+    "cookies are no longer valid",
+    "authentication",
+];
+
+/// Health of a platform's cookie file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieStatus {
+    Valid,
+    /// The last session cookie expires at this Unix time, within the warning
+    /// period.
+    ExpiresSoon(u64),
+    /// Every session cookie expired; the last one at this Unix time.
+    Expired(u64),
+    /// Unreadable or without a session cookie.
+    Invalid(String),
+}
+
+impl CookieStatus {
+    /// Status of the cookie file `text` for `platform` at Unix time `now`.
+    #[must_use]
+    pub fn check(platform: Platform, text: &str, now: u64, warning: Duration) -> Self {
+        let sessions = match cookies::parse(text).and_then(|c| cookies::sessions(platform, &c, now))
+        {
+            Ok(sessions) => sessions,
+            Err(e) => return Self::Invalid(e.to_string()),
+        };
+        // the session lasts as long as its longest-lived cookie
+        let last = sessions
+            .iter()
+            .map(|(_, expiry)| match *expiry {
+                Expiry::Session => u64::MAX,
+                Expiry::At(at) | Expiry::Expired(at) => at,
+            })
+            .max()
+            .unwrap_or_default();
+        if last <= now {
+            Self::Expired(last)
+        } else if last - now <= warning.as_secs() {
+            Self::ExpiresSoon(last)
+        } else {
+            Self::Valid
+        }
+    }
+
+    /// Admin chat alert about `account`, `None` when there is nothing to
+    /// report.
+    fn alert(&self, account: &Account, now: u64) -> Option<String> {
+        let name = account.to_string();
+        let fix = format!(
+            "Send fresh ones with /cookies {} {}.",
+            account.platform.name(),
+            account.index + 1
+        );
+        match self {
+            Self::Valid => None,
+            Self::ExpiresSoon(at) => Some(format!(
+                "{name} cookies expire on {} (in {}). {fix}",
+                format_date(*at),
+                format_days(at.saturating_sub(now))
+            )),
+            Self::Expired(at) => Some(format!(
+                "{name} cookies expired on {}; downloads will fail until they are replaced. {fix}",
+                format_date(*at)
+            )),
+            Self::Invalid(e) => Some(format!("{name} cookie file is not usable: {e}. {fix}")),
+        }
+    }
+}
+
+/// Whether a failed download asked for a login.
+#[must_use]
+pub fn is_login_required(error: &Error) -> bool {
+    let text = error
+        .stderr()
+        .map_or_else(|| error.to_string(), str::to_owned);
+    let text = text.to_lowercase();
+    LOGIN_ERRORS.iter().any(|pattern| text.contains(pattern))
+}
+
+/// Remembers what was reported per platform so each problem is reported
+/// once rather than on every check.
+#[derive(Debug, Default)]
+pub struct CookieMonitor {
+    /// Last reported status per cookie file.
+    reported: Mutex<HashMap<PathBuf, CookieStatus>>,
+    /// When a login failure was last reported.
+    login_reported: Mutex<HashMap<&'static str, Instant>>,
+}
+
+impl CookieMonitor {
+    /// Record `status`; true when it is a problem that was not reported yet.
+    fn update(&self, path: &Path, status: &CookieStatus) -> bool {
+        let mut reported = self.reported.lock().unwrap_or_else(PoisonError::into_inner);
+        let changed = if *status == CookieStatus::Valid {
+            reported.remove(path);
+            false
+        } else {
+            // a nearing expiry is only reported once, not for every change
+            // of the date
+            let same = match (reported.get(path), status) {
+                (Some(CookieStatus::ExpiresSoon(_)), CookieStatus::ExpiresSoon(_)) => true,
+                (old, new) => old == Some(new),
+            };
+            reported.insert(path.to_path_buf(), status.clone());
+            !same
+        };
+        drop(reported);
+        changed
+    }
+
+    /// Record a login failure; true when none was reported within `quiet`.
+    fn login_failed(&self, platform: Platform, now: Instant, quiet: Duration) -> bool {
+        let mut reported = self
+            .login_reported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let due = reported
+            .get(platform.name())
+            .is_none_or(|at| now.saturating_duration_since(*at) >= quiet);
+        if due {
+            reported.insert(platform.name(), now);
+        }
+        drop(reported);
+        due
+    }
+
+    /// Forget reported problems after the cookie file at `path` was
+    /// replaced.
+    pub fn reset(&self, platform: Platform, path: &Path) {
+        self.reported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(path);
+        self.login_reported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(platform.name());
+    }
+
+    /// Check the cookie file of every configured platform and alert the
+    /// admin chat about new problems.
+    pub async fn check_all(&self, bot: &Bot) {
+        let config = global_config();
+        let now = unix_now();
+        let accounts = Platform::ALL.into_iter().flat_map(|platform| {
+            let paths = platform.cookies_paths(config).iter().cloned();
+            paths.enumerate().map(move |(index, path)| Account {
+                platform,
+                index,
+                path,
+            })
+        });
+        for account in accounts {
+            let status = match fs::read_to_string(&account.path).await {
+                Ok(text) => {
+                    CookieStatus::check(account.platform, &text, now, config.cookie_expiry_warning)
+                }
+                Err(e) => CookieStatus::Invalid(format!("cannot read the file: {e}")),
+            };
+            if status != CookieStatus::Valid {
+                warn!(%account, ?status, "cookie problem");
+            }
+            if self.update(&account.path, &status)
+                && let Some(alert) = status.alert(&account, now)
+            {
+                send_alert(bot, alert).await;
+            }
+        }
+    }
+
+    /// Alert the admin chat when a download of `handler` failed because the
+    /// platform asked for a login, at most once per check interval.
+    pub async fn observe_failure(&self, bot: &Bot, handler: &str, error: &Error) {
+        let Some(platform) = Platform::parse(handler) else {
+            return;
+        };
+        if !is_login_required(error)
+            || !self.login_failed(
+                platform,
+                Instant::now(),
+                global_config().cookie_check_interval,
+            )
+        {
+            return;
+        }
+        let name = platform.name();
+        let alert = format!(
+            "{name} downloads fail asking for a login; the cookies may have expired or been revoked. Send fresh ones with /cookies {name}."
+        );
+        send_alert(bot, alert).await;
+    }
+}
+
+/// Get the global cookie monitor.
+#[must_use]
+pub fn global_monitor() -> &'static CookieMonitor {
+    GLOBAL_MONITOR.get_or_init(CookieMonitor::default)
+}
+
+/// Check the cookie files right away and then every
+/// `COOKIE_CHECK_INTERVAL_SECS`.
+pub async fn watch_cookies(bot: Bot) {
+    let mut ticker = interval(global_config().cookie_check_interval);
+    loop {
+        ticker.tick().await;
+        info!("checking cookie files");
+        global_monitor().check_all(&bot).await;
+    }
+}
+
+async fn send_alert(bot: &Bot, alert: String) {
+    if let Some(chat_id) = global_config().chat_id
+        && let Err(e) = bot.send_message(chat_id, alert).await
+    {
+        warn!("failed to send cookie alert: {e}");
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn format_days(secs: u64) -> String {
+    match secs / 86_400 {
+        0 => "less than a day".into(),
+        1 => "1 day".into(),
+        days => format!("{days} days"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_800_000_000;
+    const DAY: u64 = 86_400;
+
+    fn jar(expires: u64) -> String {
+        format!(".instagram.com\tTRUE\t/\tTRUE\t{expires}\tsessionid\tabc")
+    }
+
+    #[test]
+    fn status_and_alerts() {
+        let warning = Duration::from_hours(72);
+        let check = |text: &str| CookieStatus::check(Platform::Instagram, text, NOW, warning);
+        assert_eq!(check(&jar(NOW + 10 * DAY)), CookieStatus::Valid);
+        assert_eq!(check(&jar(0)), CookieStatus::Valid);
+        assert_eq!(
+            check(&jar(NOW + 2 * DAY)),
+            CookieStatus::ExpiresSoon(NOW + 2 * DAY)
+        );
+        assert_eq!(check(&jar(NOW - DAY)), CookieStatus::Expired(NOW - DAY));
+        assert!(matches!(check("garbage"), CookieStatus::Invalid(_)));
+
+        let monitor = CookieMonitor::default();
+        let ig = Path::new("ig.txt");
+        assert!(!monitor.update(ig, &CookieStatus::Valid));
+        assert!(monitor.update(ig, &CookieStatus::ExpiresSoon(NOW + 2 * DAY)));
+        assert!(!monitor.update(ig, &CookieStatus::ExpiresSoon(NOW + DAY)));
+        assert!(monitor.update(ig, &CookieStatus::Expired(NOW)));
+        assert!(!monitor.update(ig, &CookieStatus::Expired(NOW)));
+        assert!(!monitor.update(ig, &CookieStatus::Valid));
+        assert!(monitor.update(ig, &CookieStatus::Expired(NOW)));
+
+        let account = Account {
+            platform: Platform::Instagram,
+            index: 1,
+            path: ig.into(),
+        };
+        let alert = CookieStatus::ExpiresSoon(NOW + 2 * DAY)
+            .alert(&account, NOW)
+            .expect("alert");
+        assert!(alert.starts_with("instagram#2 (ig.txt) cookies expire"));
+        assert!(alert.contains("in 2 days") && alert.contains("/cookies instagram 2"));
+    }
+
+    #[test]
+    fn login_failures_are_throttled() {
+        let login = Error::ytdlp_failed(
+            Some(1),
+            "ERROR: [Instagram] abc: Requested content is not available, rate-limit reached or login required",
+        );
+        assert!(is_login_required(&login));
+        assert!(!is_login_required(&Error::ytdlp_failed(
+            Some(1),
+            "ERROR: Unsupported URL"
+        )));
+
+        let monitor = CookieMonitor::default();
+        let now = Instant::now();
+        let quiet = Duration::from_hours(6);
+        assert!(monitor.login_failed(Platform::Twitter, now, quiet));
+        assert!(!monitor.login_failed(Platform::Twitter, now + Duration::from_hours(1), quiet));
+        assert!(monitor.login_failed(Platform::Instagram, now, quiet));
+        monitor.reset(Platform::Twitter, Path::new("x.txt"));
+        assert!(monitor.login_failed(Platform::Twitter, now, quiet));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    error::{Error, Result},
+    template::{CaptionContext, Template},
+};
+use serde::Deserialize;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use toml::Spanned;
+
+/// Part of the day a comment fits, in the configured UTC offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeOfDay {
+    /// 05:00-11:59
+    Morning,
+    /// 12:00-16:59
+    Afternoon,
+    /// 17:00-21:59
+    Evening,
+    /// 22:00-04:59
+    Night,
+}
+
+impl TimeOfDay {
+    #[must_use]
+    pub const fn from_hour(hour: u64) -> Self {
+        match hour {
+            5..=11 => Self::Morning,
+            12..=16 => Self::Afternoon,
+            17..=21 => Self::Evening,
+            _ => Self::Night,
+        }
+    }
+
+    /// Current part of the day at `utc_offset_hours`.
+    #[must_use]
+    pub fn now(utc_offset_hours: i32) -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let hours = i64::try_from(secs / 3600).unwrap_or_default() + i64::from(utc_offset_hours);
+        Self::from_hour(hours.rem_euclid(24).unsigned_abs())
+    }
+}
+
+/// Context a comment is restricted to. Empty lists match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tags {
+    /// Handler names, e.g. `instagram`.
+    pub platform: Vec<String>,
+    /// `video` or `image`.
+    pub kind: Vec<String>,
+    pub time: Vec<TimeOfDay>,
+}
+
+impl Tags {
+    /// Whether a comment with these tags fits `ctx` at `time`. A restricted
+    /// tag never matches when the context does not know the value.
+    #[must_use]
+    pub fn matches(&self, ctx: &CaptionContext, time: TimeOfDay) -> bool {
+        fn fits<T: PartialEq>(allowed: &[T], value: Option<&T>) -> bool {
+            allowed.is_empty() || value.is_some_and(|v| allowed.contains(v))
+        }
+        let kind = ctx.kind.map(|k| k.to_str().to_owned());
+        fits(&self.platform, ctx.platform.as_ref())
+            && fits(&self.kind, kind.as_ref())
+            && fits(&self.time, Some(&time))
+    }
+}
+
+/// A comment plus its selection metadata.
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub template: Template,
+    /// Relative chance of being picked.
+    pub weight: f64,
+    pub tags: Tags,
+    pub author: Option<String>,
+    /// ID in the comment store; `None` for comments loaded from files.
+    pub id: Option<u32>,
+    /// Skipped in clean mode; set in the pack or by the profanity word list.
+    pub profane: bool,
+}
+
+impl Comment {
+    /// Untagged comment with weight 1.
+    #[must_use]
+    pub const fn plain(template: Template) -> Self {
+        Self {
+            template,
+            weight: 1.0,
+            tags: Tags {
+                platform: Vec::new(),
+                kind: Vec::new(),
+                time: Vec::new(),
+            },
+            author: None,
+            id: None,
+            profane: false,
+        }
+    }
+}
+
+/// A named set of comments chats can opt into.
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub name: String,
+    /// Language of the comments; `None` for language-neutral packs.
+    pub language: Option<String>,
+    pub comments: Vec<Comment>,
+    /// Invalid entries that were left out, with their line numbers.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackFile {
+    name: Option<String>,
+    language: Option<String>,
+    #[serde(default)]
+    comments: Vec<CommentEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommentEntry {
+    text: Spanned<String>,
+    #[serde(default = "default_weight")]
+    weight: f64,
+    #[serde(default)]
+    tags: Tags,
+    author: Option<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    profane: bool,
+}
+
+const fn default_weight() -> f64 {
+    1.0
+}
+
+const fn default_enabled() -> bool {
+    true
+}
+
+impl Pack {
+    /// Parse a TOML comment pack.
+    ///
+    /// ```toml
+    /// name = "racing"          # defaults to `default_name`
+    /// language = "lv"          # optional, see `Comments::pick`
+    ///
+    /// [[comments]]
+    /// text = "{user} found another {platform} crash"
+    /// weight = 2.0             # default 1
+    /// author = "kris"
+    /// enabled = true           # default true
+    /// profane = false          # default false, skipped in clean mode
+    /// tags = { platform = ["youtube"], kind = ["video"], time = ["night"] }
+    /// ```
+    ///
+    /// Disabled comments are dropped. Entries with a negative weight or an
+    /// invalid template are skipped and listed in `skipped`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ValidationFailed` for malformed TOML.
+    pub fn from_toml(content: &str, default_name: &str) -> Result<Self> {
+        let file: PackFile = toml::from_str(content)
+            .map_err(|e| Error::validation_falied(format!("pack `{default_name}`: {e}")))?;
+        let name = file.name.unwrap_or_else(|| default_name.to_owned());
+
+        let mut comments = Vec::new();
+        let mut skipped = Vec::new();
+        for entry in file.comments.into_iter().filter(|c| c.enabled) {
+            let line = line_of(content, entry.text.span().start);
+            if !(entry.weight.is_finite() && entry.weight >= 0.0) {
+                skipped.push(format!("line {line}: weight must be a non-negative number"));
+                continue;
+            }
+            match Template::parse(entry.text.get_ref()) {
+                Ok(template) => comments.push(Comment {
+                    template,
+                    weight: entry.weight,
+                    tags: entry.tags,
+                    author: entry.author,
+                    id: None,
+                    profane: entry.profane,
+                }),
+                Err(e) => skipped.push(format!("line {line}: {e}")),
+            }
+        }
+
+        Ok(Self {
+            name,
+            language: file.language,
+            comments,
+            skipped,
+        })
+    }
+
+    /// Load a TOML pack named after the file stem unless it sets `name`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if reading the file fails.
+    /// - Propagates `Pack::from_toml` errors.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let stem = path
+            .file_stem()
+            .map_or_else(|| "pack".into(), |s| s.to_string_lossy());
+        Self::from_toml(&content, &stem)
+    }
+}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content
+        .get(..offset)
+        .map_or(0, |before| before.matches('\n').count())
+        + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MediaKind;
+
+    const PACK: &str = r#"
+name = "racing"
+language = "lv"
+
+[[comments]]
+text = "Night crash on {platform}"
+weight = 3
+author = "kris"
+tags = { platform = ["youtube"], time = ["night"] }
+
+[[comments]]
+text = "Disabled"
+enabled = false
+
+[[comments]]
+text = "Any video"
+tags = { kind = ["video"] }
+"#;
+
+    #[test]
+    fn parses_pack() {
+        let pack = Pack::from_toml(PACK, "file").expect("valid pack");
+        assert_eq!(pack.name, "racing");
+        assert_eq!(pack.language.as_deref(), Some("lv"));
+        assert_eq!(pack.comments.len(), 2);
+        assert!((pack.comments[0].weight - 3.0).abs() < f64::EPSILON);
+        assert_eq!(pack.comments[0].author.as_deref(), Some("kris"));
+    }
+
+    #[test]
+    fn tags_match_context() {
+        let pack = Pack::from_toml(PACK, "file").expect("valid pack");
+        let ctx = CaptionContext {
+            platform: Some("youtube".into()),
+            kind: Some(MediaKind::Video),
+            ..CaptionContext::default()
+        };
+        assert!(
+            pack.comments
+                .iter()
+                .all(|c| c.tags.matches(&ctx, TimeOfDay::Night))
+        );
+        assert!(!pack.comments[0].tags.matches(&ctx, TimeOfDay::Morning));
+        assert!(pack.comments[1].tags.matches(&ctx, TimeOfDay::Morning));
+
+        let image = CaptionContext {
+            kind: Some(MediaKind::Image),
+            ..ctx
+        };
+        assert!(!pack.comments[1].tags.matches(&image, TimeOfDay::Night));
+    }
+
+    #[test]
+    fn skips_invalid_entries() {
+        let pack = Pack::from_toml(
+            "[[comments]]\ntext = \"ok\"\n\n[[comments]]\ntext = \"{bad}\"\n\n[[comments]]\ntext = \"heavy\"\nweight = -1\n",
+            "p",
+        )
+        .expect("valid TOML");
+        assert_eq!(pack.comments.len(), 1);
+        assert_eq!(pack.skipped.len(), 2);
+        assert!(pack.skipped[0].starts_with("line 5"), "{:?}", pack.skipped);
+        assert!(pack.skipped[1].starts_with("line 8"), "{:?}", pack.skipped);
+
+        Pack::from_toml("[[comments]]\ntext = ", "p").expect_err("malformed TOML");
+    }
+
+    #[test]
+    fn time_of_day_buckets() {
+        assert_eq!(TimeOfDay::from_hour(4), TimeOfDay::Night);
+        assert_eq!(TimeOfDay::from_hour(5), TimeOfDay::Morning);
+        assert_eq!(TimeOfDay::from_hour(12), TimeOfDay::Afternoon);
+        assert_eq!(TimeOfDay::from_hour(21), TimeOfDay::Evening);
+        assert_eq!(TimeOfDay::from_hour(23), TimeOfDay::Night);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::config::{RateLimitConfig, RateLimitRule};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use teloxide::types::{ChatId, UserId};
+
+/// Drop idle buckets once a map grows past this many entries.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Outcome of a rate limit check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// The request may proceed; a token was taken from every bucket.
+    Allow,
+    /// The request is over the limit. `notify` is `true` only for the first
+    /// rejection since the sender was last allowed through.
+    Throttle { notify: bool },
+}
+
+/// Classic token bucket: holds up to `capacity` tokens and refills continuously.
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(rule: RateLimitRule, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(rule.capacity),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rule: RateLimitRule, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let capacity = f64::from(rule.capacity);
+        self.tokens = elapsed
+            .mul_add(rule.refill_per_sec(), self.tokens)
+            .min(capacity);
+        self.updated = now;
+    }
+
+    fn is_full(&self, rule: RateLimitRule, now: Instant) -> bool {
+        let mut bucket = self.clone();
+        bucket.refill(rule, now);
+        bucket.tokens >= f64::from(rule.capacity)
+    }
+}
+
+#[derive(Debug)]
+struct Buckets<K> {
+    rule: Option<RateLimitRule>,
+    map: HashMap<K, TokenBucket>,
+}
+
+impl<K: Eq + Hash + Copy> Buckets<K> {
+    fn new(rule: Option<RateLimitRule>) -> Self {
+        Self {
+            rule,
+            map: HashMap::new(),
+        }
+    }
+
+    /// Whether `key` has a token available (refilling its bucket first).
+    fn has_token(&mut self, key: K, now: Instant) -> bool {
+        let Some(rule) = self.rule else {
+            return true;
+        };
+        let bucket = self
+            .map
+            .entry(key)
+            .or_insert_with(|| TokenBucket::full(rule, now));
+        bucket.refill(rule, now);
+        bucket.tokens >= 1.0
+    }
+
+    fn take(&mut self, key: K) {
+        if self.rule.is_some()
+            && let Some(bucket) = self.map.get_mut(&key)
+        {
+            bucket.tokens -= 1.0;
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let Some(rule) = self.rule else {
+            return;
+        };
+        if self.map.len() > PRUNE_THRESHOLD {
+            self.map.retain(|_, bucket| !bucket.is_full(rule, now));
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    users: Buckets<UserId>,
+    chats: Buckets<ChatId>,
+    platforms: Buckets<&'static str>,
+    /// Senders rejected since they were last allowed, with the time of the
+    /// first rejection.
+    throttled: HashMap<(ChatId, Option<UserId>), Instant>,
+}
+
+impl State {
+    /// Forget throttled senders that stayed away for a full period of every
+    /// rule; their buckets are full again, so they would be allowed anyway.
+    fn prune_throttled(&mut self, now: Instant) {
+        if self.throttled.len() <= PRUNE_THRESHOLD {
+            return;
+        }
+        let idle = [self.users.rule, self.chats.rule, self.platforms.rule]
+            .into_iter()
+            .flatten()
+            .map(|rule| rule.period)
+            .max()
+            .unwrap_or(Duration::ZERO);
+        self.throttled
+            .retain(|_, since| now.saturating_duration_since(*since) < idle);
+    }
+}
+
+/// Token-bucket rate limiter keyed per user, per chat and per platform.
+///
+/// A request is allowed only when every configured bucket has a token, in
+/// which case one token is taken from each of them.
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            state: Mutex::new(State {
+                users: Buckets::new(config.per_user),
+                chats: Buckets::new(config.per_chat),
+                platforms: Buckets::new(config.per_platform),
+                throttled: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Check (and consume) a token for a message from `user` in `chat` for `platform`.
+    pub fn check(&self, chat: ChatId, user: Option<UserId>, platform: &'static str) -> Decision {
+        self.check_at(chat, user, platform, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        chat: ChatId,
+        user: Option<UserId>,
+        platform: &'static str,
+        now: Instant,
+    ) -> Decision {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        let user_ok = user.is_none_or(|id| state.users.has_token(id, now));
+        let chat_ok = state.chats.has_token(chat, now);
+        let platform_ok = state.platforms.has_token(platform, now);
+
+        let sender = (chat, user);
+        state.prune_throttled(now);
+        if !(user_ok && chat_ok && platform_ok) {
+            let notify = !state.throttled.contains_key(&sender);
+            state.throttled.entry(sender).or_insert(now);
+            return Decision::Throttle { notify };
+        }
+
+        if let Some(id) = user {
+            state.users.take(id);
+            state.users.prune(now);
+        }
+        state.chats.take(chat);
+        state.chats.prune(now);
+        state.platforms.take(platform);
+        state.throttled.remove(&sender);
+
+        Decision::Allow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn rule(capacity: u32, secs: u64) -> RateLimitRule {
+        RateLimitRule {
+            capacity,
+            period: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn user_bucket_exhausts_and_refills() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_user: Some(rule(2, 60)),
+            ..RateLimitConfig::default()
+        });
+        let (chat, user, now) = (ChatId(1), Some(UserId(7)), Instant::now());
+
+        assert_eq!(limiter.check_at(chat, user, "tiktok", now), Decision::Allow);
+        assert_eq!(limiter.check_at(chat, user, "tiktok", now), Decision::Allow);
+        assert_eq!(
+            limiter.check_at(chat, user, "tiktok", now),
+            Decision::Throttle { notify: true }
+        );
+        assert_eq!(
+            limiter.check_at(chat, user, "tiktok", now),
+            Decision::Throttle { notify: false }
+        );
+
+        // 2 tokens per 60s => one token every 30s
+        let later = now + Duration::from_secs(30);
+        assert_eq!(
+            limiter.check_at(chat, user, "tiktok", later),
+            Decision::Allow
+        );
+        assert_eq!(
+            limiter.check_at(chat, user, "tiktok", later),
+            Decision::Throttle { notify: true }
+        );
+    }
+
+    #[test]
+    fn rejected_request_consumes_nothing() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_user: Some(rule(1, 60)),
+            per_chat: Some(rule(2, 60)),
+            ..RateLimitConfig::default()
+        });
+        let now = Instant::now();
+        let chat = ChatId(1);
+
+        assert_eq!(
+            limiter.check_at(chat, Some(UserId(1)), "x", now),
+            Decision::Allow
+        );
+        // user 1 is out of tokens, chat token must not be spent
+        assert!(matches!(
+            limiter.check_at(chat, Some(UserId(1)), "x", now),
+            Decision::Throttle { .. }
+        ));
+        assert_eq!(
+            limiter.check_at(chat, Some(UserId(2)), "x", now),
+            Decision::Allow
+        );
+        assert!(matches!(
+            limiter.check_at(chat, Some(UserId(3)), "x", now),
+            Decision::Throttle { .. }
+        ));
+    }
+
+    #[test]
+    fn prunes_throttled_senders_that_left() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_user: Some(rule(1, 60)),
+            ..RateLimitConfig::default()
+        });
+        let now = Instant::now();
+        let throttle = |id: u64| {
+            let user = Some(UserId(id));
+            limiter.check_at(ChatId(1), user, "x", now);
+            limiter.check_at(ChatId(1), user, "x", now)
+        };
+        for id in 0..=PRUNE_THRESHOLD as u64 {
+            assert_eq!(throttle(id), Decision::Throttle { notify: true });
+        }
+
+        let later = now + Duration::from_mins(1);
+        assert_eq!(
+            limiter.check_at(ChatId(2), Some(UserId(u64::MAX)), "x", later),
+            Decision::Allow
+        );
+        let throttled = limiter.state.lock().expect("not poisoned").throttled.len();
+        assert_eq!(throttled, 0);
+    }
+
+    #[test]
+    fn unconfigured_limits_allow_everything() {
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(
+                limiter.check_at(ChatId(1), Some(UserId(1)), "instagram", now),
+                Decision::Allow
+            );
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    comments::Comments,
+    config::{CommentsConfig, global_config},
+    error::{Error, Result},
+    store::global_store,
+};
+use std::{path::PathBuf, time::Duration, time::SystemTime};
+use teloxide::prelude::*;
+use tokio::{fs, time::interval};
+use tracing::{info, warn};
+
+/// How often the comment sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Re-read the comments (plus the comment store) and swap them in. The
+/// current set is kept on error.
+///
+/// Returns the number of loaded comments.
+///
+/// # Errors
+///
+/// - Propagates `Comments::load` errors.
+/// - Returns `Error::ValidationFailed` listing the problems when a source is
+///   unreadable, empty or has invalid entries, unlike the startup load which
+///   skips them.
+pub async fn reload_comments(config: &CommentsConfig) -> Result<usize> {
+    let comments = Comments::load(config, global_store().comments()).await?;
+    if !comments.problems().is_empty() {
+        return Err(Error::validation_falied(comments.problems().join("; ")));
+    }
+    let count = comments.len();
+    comments.swap();
+    info!(count, "comments reloaded");
+    Ok(count)
+}
+
+/// Reload comments when the comments file or a pack changes, or on SIGHUP.
+///
+/// Failed reloads keep the current set and are reported to the admin chat.
+pub async fn watch_comments(bot: Bot, config: CommentsConfig) {
+    let mut ticker = interval(POLL_INTERVAL);
+    let mut last = fingerprint(&config).await;
+    #[cfg(unix)]
+    let mut sighup = {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::hangup()) {
+            Ok(sighup) => Some(sighup),
+            Err(e) => {
+                warn!("cannot install SIGHUP handler: {e}");
+                None
+            }
+        }
+    };
+
+    loop {
+        #[cfg(unix)]
+        let hangup = async {
+            match sighup.as_mut() {
+                Some(sighup) => sighup.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = ticker.tick() => {
+                let current = fingerprint(&config).await;
+                if current == last {
+                    continue;
+                }
+                last = current;
+                info!("comment sources changed, reloading");
+            }
+            _ = hangup => info!("received SIGHUP, reloading comments"),
+        }
+
+        if let Err(e) = reload_comments(&config).await {
+            warn!("comment reload failed, keeping the current set: {e}");
+            if let Some(chat_id) = global_config().chat_id {
+                let report = format!("Comment reload failed, keeping the current set: {e}");
+                let _ = bot.send_message(chat_id, report).await;
+            }
+        }
+    }
+}
+
+/// Modification times of the comments file and every pack.
+async fn fingerprint(config: &CommentsConfig) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths = vec![config.file.clone()];
+    if let Ok(mut entries) = fs::read_dir(&config.packs_dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+
+    let mut stamps = Vec::with_capacity(paths.len());
+    for path in paths {
+        let modified = fs::metadata(&path)
+            .await
+            .and_then(|meta| meta.modified())
+            .ok();
+        stamps.push((path, modified));
+    }
+    stamps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fingerprint_tracks_new_packs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = CommentsConfig {
+            file: dir.path().join("comments.txt"),
+            packs_dir: dir.path().join("packs"),
+            ..CommentsConfig::default()
+        };
+        let before = fingerprint(&config).await;
+
+        fs::create_dir(&config.packs_dir).await.expect("mkdir");
+        fs::write(config.packs_dir.join("a.toml"), "")
+            .await
+            .expect("write pack");
+        assert_ne!(fingerprint(&config).await, before);
+    }
+}
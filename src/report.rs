@@ -0,0 +1,372 @@
+use crate::{config::global_config, error::Error, handler::JobId};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, MessageId, ReplyParameters},
+};
+use tokio::{process::Command, sync::OnceCell};
+use tracing::warn;
+
+static GLOBAL_REPORTER: OnceLock<Reporter> = OnceLock::new();
+static TOOL_VERSIONS: OnceCell<String> = OnceCell::const_new();
+
+/// Lines of stderr attached to a report.
+const STDERR_TAIL_LINES: usize = 40;
+/// Characters of the error message shown in the report text.
+const ERROR_PREVIEW: usize = 300;
+/// Drop expired failures once the map grows past this many entries.
+const PRUNE_THRESHOLD: usize = 256;
+
+/// A failed job, as reported to the admin chat.
+#[derive(Debug)]
+pub struct Failure<'a> {
+    pub job_id: JobId,
+    pub handler: &'a str,
+    pub url: &'a str,
+    pub msg: &'a Message,
+    pub error: &'a Error,
+}
+
+impl Failure<'_> {
+    /// Key under which identical failures are coalesced: handler, error kind,
+    /// exit code and the last error line with ids and numbers masked.
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        let text = self
+            .error
+            .stderr()
+            .map_or_else(|| self.error.to_string(), ToOwned::to_owned);
+        let line = text
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or_default();
+        let masked = line
+            .split_whitespace()
+            .map(|word| {
+                if word.contains(|c: char| c.is_ascii_digit()) || word.contains("://") {
+                    "#"
+                } else {
+                    word
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "{}|{}|{:?}|{masked}",
+            self.handler,
+            self.error.kind(),
+            self.error.exit_code()
+        )
+    }
+
+    /// Report text (without the repeat count).
+    #[must_use]
+    pub fn text(&self, versions: &str) -> String {
+        let msg = self.msg;
+        let mut text = format!("Download failed [job {}]\n", self.job_id);
+        let _ = writeln!(text, "Handler: {}", self.handler);
+        let _ = writeln!(text, "URL: {}", self.url);
+
+        let title = msg.chat.title().map_or_else(
+            || {
+                msg.chat
+                    .username()
+                    .map_or_else(|| "private chat".into(), |u| format!("@{u}"))
+            },
+            ToOwned::to_owned,
+        );
+        let _ = match msg.url() {
+            Some(link) => writeln!(text, "Chat: {title} ({}) {link}", msg.chat.id),
+            None => writeln!(text, "Chat: {title} ({})", msg.chat.id),
+        };
+        if let Some(user) = &msg.from {
+            let name = user.mention().unwrap_or_else(|| user.full_name());
+            let _ = writeln!(text, "User: {name} ({})", user.id);
+        }
+
+        let error = self.error.to_string();
+        let preview = error.chars().take(ERROR_PREVIEW).collect::<String>();
+        let ellipsis = if preview.len() < error.len() {
+            "…"
+        } else {
+            ""
+        };
+        let _ = writeln!(text, "Error ({}): {preview}{ellipsis}", self.error.kind());
+        if let Some(code) = self.error.exit_code() {
+            let _ = writeln!(text, "yt-dlp exit code: {code}");
+        }
+        let _ = write!(text, "Versions: {versions}");
+        text
+    }
+
+    /// Last lines of the captured stderr, attached as a file.
+    #[must_use]
+    pub fn stderr_tail(&self) -> Option<String> {
+        let stderr = self.error.stderr()?.trim();
+        if stderr.is_empty() {
+            return None;
+        }
+        let lines = stderr.lines().collect::<Vec<_>>();
+        let tail = &lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..];
+        Some(tail.join("\n"))
+    }
+}
+
+/// What to do with a failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Occurrence {
+    /// First in its window: send a full report.
+    New,
+    /// Seen `count` times in the window; update the report if it was sent.
+    Repeat {
+        count: u32,
+        report: Option<(MessageId, String)>,
+    },
+}
+
+#[derive(Debug)]
+struct Seen {
+    first: Instant,
+    count: u32,
+    /// Sent report message and its text.
+    report: Option<(MessageId, String)>,
+}
+
+/// Sends failure reports to the admin chat, coalescing identical failures
+/// within a window into one message with a repeat count.
+#[derive(Debug)]
+pub struct Reporter {
+    window: Duration,
+    seen: Mutex<HashMap<String, Seen>>,
+}
+
+impl Reporter {
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Report `failure` to `chat`: a new message for the first occurrence in
+    /// the window, otherwise an edit of that message with the repeat count.
+    pub async fn report(&self, bot: &Bot, chat: ChatId, failure: &Failure<'_>) {
+        let key = failure.fingerprint();
+        match self.register(&key, Instant::now()) {
+            Occurrence::New => {
+                let text = failure.text(tool_versions().await);
+                match bot.send_message(chat, text.clone()).await {
+                    Ok(sent) => {
+                        self.sent(&key, sent.id, text);
+                        if let Some(tail) = failure.stderr_tail() {
+                            let file = InputFile::memory(tail).file_name("stderr.txt");
+                            let _ = bot
+                                .send_document(chat, file)
+                                .reply_parameters(ReplyParameters::new(sent.id))
+                                .await;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("failed to send failure report: {e}");
+                        self.unsent(&key);
+                    }
+                }
+            }
+            Occurrence::Repeat {
+                count,
+                report: Some((id, text)),
+            } => {
+                let text = format!(
+                    "{text}\n\n×{count} in the last {}",
+                    format_window(self.window)
+                );
+                if let Err(e) = bot.edit_message_text(chat, id, text).await {
+                    warn!("failed to update failure report: {e}");
+                }
+            }
+            Occurrence::Repeat { report: None, .. } => {}
+        }
+    }
+
+    fn register(&self, key: &str, now: Instant) -> Occurrence {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        if seen.len() > PRUNE_THRESHOLD {
+            seen.retain(|_, s| now.saturating_duration_since(s.first) < self.window);
+        }
+        let occurrence = match seen.get_mut(key) {
+            Some(s) if now.saturating_duration_since(s.first) < self.window => {
+                s.count += 1;
+                Occurrence::Repeat {
+                    count: s.count,
+                    report: s.report.clone(),
+                }
+            }
+            _ => {
+                seen.insert(
+                    key.to_owned(),
+                    Seen {
+                        first: now,
+                        count: 1,
+                        report: None,
+                    },
+                );
+                Occurrence::New
+            }
+        };
+        drop(seen);
+        occurrence
+    }
+
+    fn sent(&self, key: &str, id: MessageId, text: String) {
+        if let Some(seen) = self
+            .seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(key)
+        {
+            seen.report = Some((id, text));
+        }
+    }
+
+    /// Forget a failure whose report could not be sent, so the next
+    /// occurrence is reported in full.
+    fn unsent(&self, key: &str) {
+        self.seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+}
+
+/// Get the global reporter, created with the configured window on first use.
+#[must_use]
+pub fn global_reporter() -> &'static Reporter {
+    GLOBAL_REPORTER.get_or_init(|| Reporter::new(global_config().report_window))
+}
+
+/// "yt-dlp 2025.01.01, ffmpeg 7.1", detected once.
+async fn tool_versions() -> &'static str {
+    TOOL_VERSIONS
+        .get_or_init(|| async {
+            let ytdlp = version("yt-dlp", "--version").await;
+            let ffmpeg = version("ffmpeg", "-version").await;
+            format!("yt-dlp {ytdlp}, ffmpeg {ffmpeg}")
+        })
+        .await
+}
+
+/// First line of `cmd arg` without the leading program name.
+async fn version(cmd: &str, arg: &str) -> String {
+    let Ok(output) = Command::new(cmd).arg(arg).output().await else {
+        return "unavailable".into();
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next().unwrap_or_default();
+    // `ffmpeg version 7.1 Copyright ...`
+    line.strip_prefix("ffmpeg version ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or(line)
+        .trim()
+        .to_owned()
+}
+
+fn format_window(window: Duration) -> String {
+    match window.as_secs() {
+        3600 => "hour".into(),
+        secs if secs % 3600 == 0 => format!("{} hours", secs / 3600),
+        secs if secs % 60 == 0 => format!("{} min", secs / 60),
+        secs => format!("{secs} s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure_key(error: &Error) -> String {
+        let msg = serde_json::from_value::<Message>(serde_json::json!({
+            "message_id": 7,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup", "title": "Pit wall"},
+            "from": {"id": 1, "is_bot": false, "first_name": "Kris"},
+            "text": "https://youtu.be/abc"
+        }))
+        .expect("valid message");
+        Failure {
+            job_id: JobId::generate(),
+            handler: "youtube",
+            url: "https://youtu.be/abc",
+            msg: &msg,
+            error,
+        }
+        .fingerprint()
+    }
+
+    #[test]
+    fn fingerprint_masks_ids() {
+        let a = Error::ytdlp_failed(
+            Some(1),
+            "WARNING: x\nERROR: [youtube] abc123: Video unavailable",
+        );
+        let b = Error::ytdlp_failed(Some(1), "ERROR: [youtube] zz9zz: Video unavailable\n");
+        let other = Error::ytdlp_failed(Some(2), "ERROR: [youtube] abc123: Video unavailable");
+        assert_eq!(failure_key(&a), failure_key(&b));
+        assert_ne!(failure_key(&a), failure_key(&other));
+    }
+
+    #[test]
+    fn coalesces_within_window() {
+        let reporter = Reporter::new(Duration::from_hours(1));
+        let now = Instant::now();
+        assert_eq!(reporter.register("a", now), Occurrence::New);
+        assert_eq!(
+            reporter.register("a", now),
+            Occurrence::Repeat {
+                count: 2,
+                report: None
+            }
+        );
+        reporter.sent("a", MessageId(5), "report".into());
+        assert_eq!(
+            reporter.register("a", now + Duration::from_mins(59)),
+            Occurrence::Repeat {
+                count: 3,
+                report: Some((MessageId(5), "report".into()))
+            }
+        );
+        assert_eq!(reporter.register("b", now), Occurrence::New);
+        assert_eq!(
+            reporter.register("a", now + Duration::from_hours(1)),
+            Occurrence::New
+        );
+        assert_eq!(format_window(Duration::from_hours(1)), "hour");
+        assert_eq!(format_window(Duration::from_mins(10)), "10 min");
+    }
+
+    #[test]
+    fn retries_unsent_reports() {
+        let reporter = Reporter::new(Duration::from_hours(1));
+        let now = Instant::now();
+        assert_eq!(reporter.register("a", now), Occurrence::New);
+        reporter.unsent("a");
+        assert_eq!(
+            reporter.register("a", now + Duration::from_mins(1)),
+            Occurrence::New
+        );
+        reporter.sent("a", MessageId(5), "report".into());
+        assert_eq!(
+            reporter.register("a", now + Duration::from_mins(2)),
+            Occurrence::Repeat {
+                count: 2,
+                report: Some((MessageId(5), "report".into()))
+            }
+        );
+    }
+}
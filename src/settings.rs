@@ -0,0 +1,167 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{OnceLock, PoisonError, RwLock},
+};
+use teloxide::types::ChatId;
+use tokio::fs;
+use tracing::warn;
+
+static GLOBAL_SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Per-chat preferences changed through bot commands.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatSettings {
+    /// Delete the original link message and repost the media with attribution.
+    pub replace: bool,
+    /// Comment packs to pick from; empty means all packs.
+    pub packs: Vec<String>,
+    /// Bot language; `None` follows the sender's Telegram language.
+    pub language: Option<String>,
+    /// Family-friendly mode: polite messages and no profane comments.
+    pub clean: bool,
+}
+
+/// Per-chat settings, persisted as JSON so they survive restarts.
+#[derive(Debug)]
+pub struct Settings {
+    path: Option<PathBuf>,
+    chats: RwLock<HashMap<i64, ChatSettings>>,
+    /// Serializes writes so an older snapshot never replaces a newer one.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl Settings {
+    /// In-memory settings that are never written to disk.
+    #[must_use]
+    pub fn ephemeral() -> Self {
+        Self {
+            path: None,
+            chats: RwLock::default(),
+            write_lock: tokio::sync::Mutex::const_new(()),
+        }
+    }
+
+    /// Load settings from `path`. A missing file yields empty settings.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the file exists but cannot be read.
+    /// - Returns `Error::Other` if the file is not valid JSON.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let chats = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::other(format!("invalid settings file: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            chats: RwLock::new(chats),
+            ..Self::ephemeral()
+        })
+    }
+
+    /// Settings for `chat` (defaults when never changed).
+    #[must_use]
+    pub fn get(&self, chat: ChatId) -> ChatSettings {
+        self.chats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&chat.0)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Modify the settings of `chat` and persist all settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the settings file fails.
+    pub async fn update(&self, chat: ChatId, f: impl FnOnce(&mut ChatSettings)) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = {
+            let mut chats = self.chats.write().unwrap_or_else(PoisonError::into_inner);
+            let settings = chats.entry(chat.0).or_default();
+            f(settings);
+            if *settings == ChatSettings::default() {
+                chats.remove(&chat.0);
+            }
+            serde_json::to_string_pretty(&*chats)
+                .map_err(|e| Error::other(format!("failed to serialize settings: {e}")))?
+        };
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_atomic(path, &snapshot).await
+    }
+
+    /// Initialize the global settings (call once at startup).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Other` when the global is already initialized.
+    pub fn init(self) -> Result<()> {
+        GLOBAL_SETTINGS
+            .set(self)
+            .map_err(|_| Error::other("settings already initialized"))
+    }
+}
+
+/// Get global settings (initialized by `Settings::init(self)`).
+///
+/// # Panics
+///
+/// Panics if settings have not been initialized.
+#[inline]
+#[must_use]
+pub fn global_settings() -> &'static Settings {
+    GLOBAL_SETTINGS.get().expect("settings not initialized")
+}
+
+/// Write `content` next to `path` and rename it into place.
+///
+/// # Errors
+///
+/// Returns `Error::Io` if creating, writing or renaming the file fails.
+pub async fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content).await?;
+    if let Err(e) = fs::rename(&tmp, path).await {
+        warn!(path = %path.display(), "failed to replace file: {e}");
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn update_persists_and_reloads() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("settings.json");
+
+        let settings = Settings::load(&path).await.expect("missing file is fine");
+        assert!(!settings.get(ChatId(-100)).replace);
+
+        settings
+            .update(ChatId(-100), |s| s.replace = true)
+            .await
+            .expect("write settings");
+
+        let reloaded = Settings::load(&path).await.expect("reload");
+        assert!(reloaded.get(ChatId(-100)).replace);
+        assert!(!reloaded.get(ChatId(1)).replace);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{future::Future, time::Duration};
+use teloxide::dispatching::ShutdownToken;
+use tokio::time::{sleep, timeout};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+/// Cancellation handle shared by every in-flight job.
+///
+/// Jobs run through [`Jobs::run`] are dropped once [`Jobs::cancel`] is called.
+/// Dropping a job kills its yt-dlp subprocess (`kill_on_drop`) and removes its
+/// temporary directory (`TempDir` drop).
+///
+/// Work that outlives its update handler (inline uploads) is started with
+/// [`Jobs::spawn`] so the shutdown waits for it too.
+#[derive(Debug, Clone, Default)]
+pub struct Jobs {
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Jobs {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `job` until it completes or the jobs are cancelled.
+    ///
+    /// Returns `None` when the job was cancelled.
+    pub async fn run<F: Future>(&self, job: F) -> Option<F::Output> {
+        tokio::select! {
+            output = job => Some(output),
+            () = self.token.cancelled() => None,
+        }
+    }
+
+    /// Spawn a background task that the shutdown waits for.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    /// Cancel every running (and future) job.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Wait until every spawned task has finished.
+    async fn wait(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+}
+
+/// Resolve once SIGTERM or SIGINT is received.
+///
+/// # Panics
+///
+/// Panics if the signal handlers cannot be installed.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut sigterm = signal(SignalKind::terminate()).expect("install SIGTERM handler");
+        tokio::select! {
+            _ = sigterm.recv() => info!("received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => info!("received SIGINT"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("received ctrl-c");
+    }
+}
+
+/// Wait for a termination signal, then stop the dispatcher from taking new
+/// updates and give in-flight jobs and spawned tasks `grace` to finish
+/// before cancelling them.
+pub async fn graceful_shutdown(token: ShutdownToken, jobs: Jobs, grace: Duration) {
+    wait_for_signal().await;
+    info!(grace = ?grace, "shutting down, draining in-flight jobs");
+
+    // `shutdown` fails while the dispatcher has not started yet
+    let drained = loop {
+        match token.shutdown() {
+            Ok(drained) => break drained,
+            Err(_) => sleep(Duration::from_millis(100)).await,
+        }
+    };
+
+    let finished = async {
+        drained.await;
+        jobs.wait().await;
+    };
+    if timeout(grace, finished).await.is_err() {
+        warn!("grace period elapsed, cancelling remaining jobs");
+        jobs.cancel();
+        // cancelled tasks only clean up from here
+        jobs.wait().await;
+    } else {
+        info!("all jobs finished");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled_job_is_dropped() {
+        let jobs = Jobs::new();
+        assert_eq!(jobs.run(async { 42 }).await, Some(42));
+
+        jobs.cancel();
+        let pending = jobs.run(std::future::pending::<()>()).await;
+        assert!(pending.is_none());
+    }
+
+    #[tokio::test]
+    async fn waits_for_spawned_tasks() {
+        let jobs = Jobs::new();
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        let task_jobs = jobs.clone();
+        jobs.spawn(async move {
+            let cancelled = task_jobs.run(std::future::pending::<()>()).await;
+            let _ = tx.send(cancelled.is_none());
+        });
+
+        assert!(
+            timeout(Duration::from_millis(50), jobs.wait())
+                .await
+                .is_err()
+        );
+        jobs.cancel();
+        jobs.wait().await;
+        assert_eq!(rx.try_recv(), Ok(true));
+    }
+}
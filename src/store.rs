@@ -0,0 +1,315 @@
+use crate::{
+    error::{Error, Result},
+    packs::Comment,
+    settings::write_atomic,
+    template::Template,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{OnceLock, PoisonError, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use teloxide::types::UserId;
+use tokio::{fs, sync::Mutex};
+
+static GLOBAL_STORE: OnceLock<CommentStore> = OnceLock::new();
+
+/// A comment added through `/addcomment`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredComment {
+    pub id: u32,
+    pub text: String,
+    /// Display name of the user who added it.
+    pub added_by: String,
+    pub added_by_id: UserId,
+    /// Unix timestamp in seconds.
+    pub added_at: u64,
+}
+
+impl StoredComment {
+    /// Selectable comment for the `default` pack.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ValidationFailed` if the text is not a valid template.
+    pub fn to_comment(&self) -> Result<Comment> {
+        let template = Template::parse(&self.text)
+            .map_err(|e| Error::validation_falied(format!("comment #{}: {e}", self.id)))?;
+        Ok(Comment {
+            id: Some(self.id),
+            author: Some(self.added_by.clone()),
+            ..Comment::plain(template)
+        })
+    }
+
+    /// `added_at` as `YYYY-MM-DD` (UTC).
+    #[must_use]
+    pub fn added_on(&self) -> String {
+        format_date(self.added_at)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoreFile {
+    next_id: u32,
+    comments: Vec<StoredComment>,
+}
+
+/// Comments managed from Telegram, persisted as JSON and merged into the
+/// `default` pack on every (re)load.
+#[derive(Debug)]
+pub struct CommentStore {
+    path: Option<PathBuf>,
+    file: RwLock<StoreFile>,
+    /// Serializes changes so an older snapshot never replaces a newer one.
+    write_lock: Mutex<()>,
+}
+
+impl CommentStore {
+    /// In-memory store that is never written to disk.
+    #[must_use]
+    pub fn ephemeral() -> Self {
+        Self {
+            path: None,
+            file: RwLock::default(),
+            write_lock: Mutex::const_new(()),
+        }
+    }
+
+    /// Load the store from `path`. A missing file yields an empty store.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the file exists but cannot be read.
+    /// - Returns `Error::Other` if the file is not valid JSON.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::other(format!("invalid comment store: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoreFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            file: RwLock::new(file),
+            write_lock: Mutex::const_new(()),
+        })
+    }
+
+    /// All stored comments, oldest first.
+    #[must_use]
+    pub fn list(&self) -> Vec<StoredComment> {
+        self.file
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .comments
+            .clone()
+    }
+
+    /// Stored comments as selectable comments. Invalid entries are skipped.
+    #[must_use]
+    pub fn comments(&self) -> Vec<Comment> {
+        self.file
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .comments
+            .iter()
+            .filter_map(|c| c.to_comment().ok())
+            .collect()
+    }
+
+    /// Validate and store `text`.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::ValidationFailed` if `text` is empty or not a valid template.
+    /// - Returns `Error::Io` if writing the store fails; the comment is not
+    ///   added then.
+    pub async fn add(&self, text: &str, added_by: String, added_by_id: UserId) -> Result<u32> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::validation_falied("comment is empty"));
+        }
+        Template::parse(text).map_err(|e| Error::validation_falied(e.to_string()))?;
+
+        let added_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let _guard = self.write_lock.lock().await;
+        let (id, file, snapshot) = self.modify(|file| {
+            file.next_id = file.next_id.max(1);
+            let id = file.next_id;
+            file.next_id += 1;
+            file.comments.push(StoredComment {
+                id,
+                text: text.to_owned(),
+                added_by,
+                added_by_id,
+                added_at,
+            });
+            id
+        })?;
+        self.persist(&snapshot).await?;
+        self.commit(file);
+        Ok(id)
+    }
+
+    /// Remove the comment with `id`, returning it if it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the store fails; the comment is kept
+    /// then.
+    pub async fn remove(&self, id: u32) -> Result<Option<StoredComment>> {
+        let _guard = self.write_lock.lock().await;
+        let (removed, file, snapshot) = self.modify(|file| {
+            let index = file.comments.iter().position(|c| c.id == id)?;
+            Some(file.comments.remove(index))
+        })?;
+        if removed.is_some() {
+            self.persist(&snapshot).await?;
+            self.commit(file);
+        }
+        Ok(removed)
+    }
+
+    /// Apply `f` to a copy of the store and serialize it for persisting.
+    /// The copy takes effect with [`CommentStore::commit`] once written.
+    fn modify<T>(&self, f: impl FnOnce(&mut StoreFile) -> T) -> Result<(T, StoreFile, String)> {
+        let mut file = self
+            .file
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let output = f(&mut file);
+        let snapshot = serde_json::to_string_pretty(&file)
+            .map_err(|e| Error::other(format!("failed to serialize comment store: {e}")))?;
+        Ok((output, file, snapshot))
+    }
+
+    fn commit(&self, file: StoreFile) {
+        *self.file.write().unwrap_or_else(PoisonError::into_inner) = file;
+    }
+
+    async fn persist(&self, snapshot: &str) -> Result<()> {
+        match &self.path {
+            Some(path) => write_atomic(path, snapshot).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Initialize the global store (call once at startup).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Other` when the global is already initialized.
+    pub fn init(self) -> Result<()> {
+        GLOBAL_STORE
+            .set(self)
+            .map_err(|_| Error::other("comment store already initialized"))
+    }
+}
+
+/// Get the global comment store (initialized by `CommentStore::init(self)`).
+///
+/// # Panics
+///
+/// Panics if the store has not been initialized.
+#[inline]
+#[must_use]
+pub fn global_store() -> &'static CommentStore {
+    GLOBAL_STORE.get().expect("comment store not initialized")
+}
+
+/// Format a Unix timestamp as a `YYYY-MM-DD` UTC date.
+#[must_use]
+pub fn format_date(secs: u64) -> String {
+    // days-to-civil from Howard Hinnant's date algorithms
+    let z = secs / 86_400 + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn add_remove_and_reload() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("comments.json");
+
+        let store = CommentStore::load(&path)
+            .await
+            .expect("missing file is fine");
+        let first = store
+            .add("Nice one, {user}", "@kris".into(), UserId(1))
+            .await
+            .expect("valid comment");
+        let second = store
+            .add("Another", "@kris".into(), UserId(1))
+            .await
+            .expect("valid comment");
+        assert!(
+            store
+                .add("{nope}", "@kris".into(), UserId(1))
+                .await
+                .is_err()
+        );
+
+        let removed = store.remove(first).await.expect("write store");
+        assert_eq!(removed.map(|c| c.text), Some("Nice one, {user}".into()));
+
+        let reloaded = CommentStore::load(&path).await.expect("reload");
+        let list = reloaded.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, second);
+        assert_eq!(list[0].added_by_id, UserId(1));
+    }
+
+    #[tokio::test]
+    async fn failed_writes_change_nothing() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut store = CommentStore::load(dir.path().join("comments.json"))
+            .await
+            .expect("missing file is fine");
+        let id = store
+            .add("Kept", "@kris".into(), UserId(1))
+            .await
+            .expect("valid comment");
+
+        // a file in place of the directory makes every write fail
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, "").expect("create");
+        store.path = Some(blocker.join("comments.json"));
+        assert!(store.add("Lost", "@kris".into(), UserId(1)).await.is_err());
+        assert!(store.remove(id).await.is_err());
+        let list = store.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].text, "Kept");
+
+        // the id of the failed add is not used up
+        store.path = None;
+        let next = store
+            .add("Next", "@kris".into(), UserId(1))
+            .await
+            .expect("valid comment");
+        assert_eq!(next, id + 1);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_792_281_600), "2026-10-18");
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    error::{Error, Result},
+    settings::write_atomic,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+use teloxide::types::{ChatId, User, UserId};
+use tokio::fs;
+
+static GLOBAL_TARGETS: OnceLock<CurseTargets> = OnceLock::new();
+
+/// Drop expired cooldowns once the map grows past this many entries.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Someone a `/curse` is aimed at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A known user (replied to or mentioned without a username).
+    User(User),
+    /// An `@username` mention, stored without the `@`.
+    Username(String),
+}
+
+impl Target {
+    /// Parse an `@username` argument.
+    #[must_use]
+    pub fn from_mention(arg: &str) -> Option<Self> {
+        let name = arg.strip_prefix('@')?;
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        valid.then(|| Self::Username(name.to_owned()))
+    }
+
+    /// Lowercase username, if known.
+    #[must_use]
+    pub fn username(&self) -> Option<String> {
+        match self {
+            Self::User(user) => user.username.as_deref().map(str::to_lowercase),
+            Self::Username(name) => Some(name.to_lowercase()),
+        }
+    }
+
+    /// How the target is addressed in comments.
+    #[must_use]
+    pub fn mention(&self) -> String {
+        match self {
+            Self::User(user) => user.mention().unwrap_or_else(|| user.full_name()),
+            Self::Username(name) => format!("@{name}"),
+        }
+    }
+
+    /// Cooldown key: the username when known, so replies and mentions of the
+    /// same person share a cooldown.
+    fn key(&self) -> String {
+        match (self, self.username()) {
+            (_, Some(name)) => name,
+            (Self::User(user), None) => user.id.to_string(),
+            (Self::Username(name), None) => name.clone(),
+        }
+    }
+}
+
+/// A user who does not want to be targeted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct OptOut {
+    id: UserId,
+    /// Lowercase username at the time of opting out, to match `@mentions`.
+    username: Option<String>,
+}
+
+/// Opt-outs (persisted as JSON) and per-chat cooldowns for targeted `/curse`.
+#[derive(Debug)]
+pub struct CurseTargets {
+    path: Option<PathBuf>,
+    opted_out: RwLock<Vec<OptOut>>,
+    /// Last time each target was cursed, per chat.
+    cursed: Mutex<HashMap<(ChatId, String), Instant>>,
+    /// Serializes writes so an older snapshot never replaces a newer one.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl CurseTargets {
+    /// In-memory targets that are never written to disk.
+    #[must_use]
+    pub fn ephemeral() -> Self {
+        Self {
+            path: None,
+            opted_out: RwLock::default(),
+            cursed: Mutex::default(),
+            write_lock: tokio::sync::Mutex::const_new(()),
+        }
+    }
+
+    /// Load opt-outs from `path`. A missing file yields no opt-outs.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Error::Io` if the file exists but cannot be read.
+    /// - Returns `Error::Other` if the file is not valid JSON.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let opted_out = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::other(format!("invalid curse opt-out file: {e}")))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            opted_out: RwLock::new(opted_out),
+            ..Self::ephemeral()
+        })
+    }
+
+    /// Whether `target` opted out of being targeted.
+    #[must_use]
+    pub fn is_opted_out(&self, target: &Target) -> bool {
+        let id = match target {
+            Target::User(user) => Some(user.id),
+            Target::Username(_) => None,
+        };
+        let username = target.username();
+        self.opted_out
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|o| Some(o.id) == id || (o.username.is_some() && o.username == username))
+    }
+
+    /// Opt `user` out of (or back into) being targeted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if writing the file fails.
+    pub async fn set_opted_out(&self, user: &User, opted_out: bool) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = {
+            let mut list = self
+                .opted_out
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            list.retain(|o| o.id != user.id);
+            if opted_out {
+                list.push(OptOut {
+                    id: user.id,
+                    username: user.username.as_deref().map(str::to_lowercase),
+                });
+            }
+            serde_json::to_string_pretty(&*list)
+                .map_err(|e| Error::other(format!("failed to serialize curse opt-outs: {e}")))?
+        };
+        match &self.path {
+            Some(path) => write_atomic(path, &snapshot).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Start the cooldown for `target` in `chat`, or return how long it still
+    /// runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the remaining cooldown when `target` was cursed in `chat`
+    /// less than `cooldown` ago.
+    pub fn try_curse(
+        &self,
+        chat: ChatId,
+        target: &Target,
+        cooldown: Duration,
+        now: Instant,
+    ) -> std::result::Result<(), Duration> {
+        let mut cursed = self.cursed.lock().unwrap_or_else(PoisonError::into_inner);
+        if cursed.len() > PRUNE_THRESHOLD {
+            cursed.retain(|_, at| now.saturating_duration_since(*at) < cooldown);
+        }
+        let key = (chat, target.key());
+        let left = cursed
+            .get(&key)
+            .and_then(|at| cooldown.checked_sub(now.saturating_duration_since(*at)))
+            .filter(|left| !left.is_zero());
+        if left.is_none() {
+            cursed.insert(key, now);
+        }
+        drop(cursed);
+        left.map_or(Ok(()), Err)
+    }
+
+    /// Initialize the global targets (call once at startup).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Other` when the global is already initialized.
+    pub fn init(self) -> Result<()> {
+        GLOBAL_TARGETS
+            .set(self)
+            .map_err(|_| Error::other("curse targets already initialized"))
+    }
+}
+
+/// Get the global curse targets (initialized by `CurseTargets::init(self)`).
+///
+/// # Panics
+///
+/// Panics if the targets have not been initialized.
+#[inline]
+#[must_use]
+pub fn global_targets() -> &'static CurseTargets {
+    GLOBAL_TARGETS.get().expect("curse targets not initialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64, username: &str) -> User {
+        User {
+            id: UserId(id),
+            is_bot: false,
+            first_name: "Kris".into(),
+            last_name: None,
+            username: Some(username.into()),
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn opt_out_and_cooldown() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("optout.json");
+        let targets = CurseTargets::load(&path)
+            .await
+            .expect("missing file is fine");
+
+        let kris = Target::User(user(1, "Kris"));
+        let mention = Target::from_mention("@kris").expect("valid mention");
+        assert_eq!(Target::from_mention("kris"), None);
+
+        let chat = ChatId(-100);
+        let cooldown = Duration::from_mins(1);
+        let now = Instant::now();
+        assert_eq!(targets.try_curse(chat, &kris, cooldown, now), Ok(()));
+        assert_eq!(
+            targets.try_curse(chat, &mention, cooldown, now + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(
+            targets.try_curse(ChatId(-200), &mention, cooldown, now),
+            Ok(())
+        );
+        assert_eq!(
+            targets.try_curse(chat, &mention, cooldown, now + cooldown),
+            Ok(())
+        );
+
+        targets
+            .set_opted_out(&user(1, "Kris"), true)
+            .await
+            .expect("write opt-outs");
+        let reloaded = CurseTargets::load(&path).await.expect("reload");
+        assert!(reloaded.is_opted_out(&kris));
+        assert!(reloaded.is_opted_out(&mention));
+        assert!(!reloaded.is_opted_out(&Target::Username("someone".into())));
+
+        reloaded
+            .set_opted_out(&user(1, "Kris"), false)
+            .await
+            .expect("write opt-outs");
+        assert!(!reloaded.is_opted_out(&mention));
+    }
+}
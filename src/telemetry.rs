@@ -1,12 +1,75 @@
-use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+use crate::config::{LogConfig, LogFormat};
+use tracing::{Subscriber, warn};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, fmt, layer::SubscriberExt, registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Keeps the non-blocking log writers flushing. Hold it for the whole
+/// process lifetime; dropping it flushes and stops the background writers.
+#[derive(Debug)]
+#[must_use = "dropping the guard stops log output"]
+pub struct LoggerGuard {
+    _guards: Vec<WorkerGuard>,
+}
 
 /// Initialise tracing
-pub fn setup_logger() {
+pub fn setup_logger(config: &LogConfig) -> LoggerGuard {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
-    let formatter = tracing_subscriber::fmt::Layer::default();
+
+    let mut guards = Vec::new();
+    let mut layers: Vec<BoxedLayer<Registry>> = Vec::new();
+    let mut file_error = None;
+
+    if config.stdout {
+        let (writer, guard) = tracing_appender::non_blocking(std::io::stdout());
+        guards.push(guard);
+        layers.push(fmt_layer(config.format, writer, true));
+    }
+
+    if let Some(dir) = &config.dir {
+        match RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("tg-relay")
+            .filename_suffix("log")
+            .max_log_files(config.retention_days)
+            .build(dir)
+        {
+            Ok(appender) => {
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                guards.push(guard);
+                layers.push(fmt_layer(config.format, writer, false));
+            }
+            Err(e) => file_error = Some(e),
+        }
+    }
 
     tracing_subscriber::registry()
+        .with(layers)
         .with(env_filter)
-        .with(formatter)
         .init();
+
+    if let Some(e) = file_error {
+        warn!(dir = ?config.dir, "failed to set up file logging: {e}");
+    }
+
+    LoggerGuard { _guards: guards }
+}
+
+fn fmt_layer<S>(format: LogFormat, writer: NonBlocking, ansi: bool) -> BoxedLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
 }
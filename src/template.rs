@@ -0,0 +1,315 @@
+use crate::{download::MediaInfo, utils::MediaKind};
+use std::{fmt::Display, str::FromStr};
+use teloxide::types::{ChatId, Message, User};
+use thiserror::Error;
+
+/// Values a comment line can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `@username`, or the full name when the sender has no username.
+    User,
+    FirstName,
+    /// Handler name, e.g. `instagram`.
+    Platform,
+    Title,
+    Uploader,
+    /// Media length as `m:ss`.
+    Duration,
+    /// Chat title.
+    Chat,
+}
+
+impl Placeholder {
+    const ALL: &[Self] = &[
+        Self::User,
+        Self::FirstName,
+        Self::Platform,
+        Self::Title,
+        Self::Uploader,
+        Self::Duration,
+        Self::Chat,
+    ];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::FirstName => "first_name",
+            Self::Platform => "platform",
+            Self::Title => "title",
+            Self::Uploader => "uploader",
+            Self::Duration => "duration",
+            Self::Chat => "chat",
+        }
+    }
+}
+
+impl FromStr for Placeholder {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| TemplateError::UnknownPlaceholder(s.to_owned()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("unknown placeholder `{{{0}}}`")]
+    UnknownPlaceholder(String),
+
+    #[error("unclosed `{{`")]
+    Unclosed,
+
+    #[error("unmatched `}}` (write `}}}}` for a literal brace)")]
+    Unmatched,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Value {
+        key: Placeholder,
+        fallback: Option<String>,
+    },
+}
+
+/// A comment line with `{placeholder}` and `{placeholder|fallback}` slots.
+///
+/// `{{` and `}}` produce literal braces. A placeholder without a fallback
+/// renders as nothing when its value is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parse a template line.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TemplateError` for unknown placeholders or unbalanced braces.
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(TemplateError::Unmatched),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(TemplateError::Unclosed),
+                            Some(c) => inner.push(c),
+                        }
+                    }
+                    let (key, fallback) = match inner.split_once('|') {
+                        Some((key, fallback)) => (key, Some(fallback.to_owned())),
+                        None => (inner.as_str(), None),
+                    };
+                    let key = key.trim().parse()?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Value { key, fallback });
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self {
+            source: source.to_owned(),
+            parts,
+        })
+    }
+
+    /// The line as written in the comments file.
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether every placeholder without a fallback has a value in `ctx`.
+    #[must_use]
+    pub fn is_satisfied_by(&self, ctx: &CaptionContext) -> bool {
+        self.parts.iter().all(|part| match part {
+            Part::Value {
+                key,
+                fallback: None,
+            } => ctx.get(*key).is_some(),
+            _ => true,
+        })
+    }
+
+    /// Fill in the placeholders from `ctx`.
+    #[must_use]
+    pub fn render(&self, ctx: &CaptionContext) -> String {
+        let mut out = String::with_capacity(self.source.len());
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Value { key, fallback } => {
+                    if let Some(value) = ctx.get(*key).or(fallback.as_deref()) {
+                        out.push_str(value);
+                    }
+                }
+            }
+        }
+        out.trim().to_owned()
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Values available when picking and rendering a comment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptionContext {
+    pub user: Option<String>,
+    pub first_name: Option<String>,
+    pub platform: Option<String>,
+    pub title: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<String>,
+    pub chat: Option<String>,
+    /// Kind of the media being captioned; `None` for plain text.
+    pub kind: Option<MediaKind>,
+    /// Comment packs the chat opted into; empty means all packs.
+    pub packs: Vec<String>,
+    /// Chat the comment is for, used to avoid repeats; `None` for inline answers.
+    pub chat_id: Option<ChatId>,
+    /// Language of the chat, used to pick per-language packs.
+    pub lang: Option<String>,
+    /// Skip profane comments (clean mode).
+    pub clean: bool,
+    /// Link the media was fetched from.
+    pub source: Option<String>,
+}
+
+impl CaptionContext {
+    #[must_use]
+    pub fn get(&self, key: Placeholder) -> Option<&str> {
+        let value = match key {
+            Placeholder::User => &self.user,
+            Placeholder::FirstName => &self.first_name,
+            Placeholder::Platform => &self.platform,
+            Placeholder::Title => &self.title,
+            Placeholder::Uploader => &self.uploader,
+            Placeholder::Duration => &self.duration,
+            Placeholder::Chat => &self.chat,
+        };
+        value.as_deref().filter(|v| !v.trim().is_empty())
+    }
+
+    /// Context describing the sender and chat of `msg`.
+    #[must_use]
+    pub fn from_message(msg: &Message) -> Self {
+        Self {
+            chat: msg.chat.title().map(ToOwned::to_owned),
+            chat_id: Some(msg.chat.id),
+            ..msg.from.as_ref().map(Self::from_user).unwrap_or_default()
+        }
+    }
+
+    /// Context describing `user`.
+    #[must_use]
+    pub fn from_user(user: &User) -> Self {
+        Self {
+            user: Some(user.mention().unwrap_or_else(|| user.full_name())),
+            first_name: Some(user.first_name.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// Add title, uploader and duration from yt-dlp metadata.
+    #[must_use]
+    pub fn with_media(mut self, info: &MediaInfo) -> Self {
+        self.title.clone_from(&info.title);
+        self.uploader.clone_from(&info.uploader);
+        self.duration = info.duration.map(Self::format_duration);
+        self
+    }
+
+    /// Format a length in seconds as `m:ss` (or `h:mm:ss`).
+    #[must_use]
+    pub fn format_duration(seconds: f64) -> String {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let total = seconds.max(0.0).round() as u64;
+        let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+        if h > 0 {
+            format!("{h}:{m:02}:{s:02}")
+        } else {
+            format!("{m}:{s:02}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> CaptionContext {
+        CaptionContext {
+            user: Some("@kris".into()),
+            platform: Some("tiktok".into()),
+            ..CaptionContext::default()
+        }
+    }
+
+    #[test]
+    fn renders_values_and_fallbacks() {
+        let t = Template::parse("{user} sent a {platform} clip: {title|no title}").expect("valid");
+        assert_eq!(t.render(&ctx()), "@kris sent a tiktok clip: no title");
+        assert!(t.is_satisfied_by(&ctx()));
+    }
+
+    #[test]
+    fn missing_value_without_fallback() {
+        let t = Template::parse("By {uploader}.").expect("valid");
+        assert!(!t.is_satisfied_by(&ctx()));
+        assert_eq!(t.render(&ctx()), "By .");
+    }
+
+    #[test]
+    fn literal_braces() {
+        let t = Template::parse("{{not a placeholder}}").expect("valid");
+        assert_eq!(t.render(&ctx()), "{not a placeholder}");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert_eq!(
+            Template::parse("{nope}"),
+            Err(TemplateError::UnknownPlaceholder("nope".into()))
+        );
+        assert_eq!(Template::parse("{user"), Err(TemplateError::Unclosed));
+        assert_eq!(Template::parse("user}"), Err(TemplateError::Unmatched));
+    }
+
+    #[test]
+    fn duration_format() {
+        assert_eq!(CaptionContext::format_duration(65.4), "1:05");
+        assert_eq!(CaptionContext::format_duration(3723.0), "1:02:03");
+    }
+}
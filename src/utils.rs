@@ -1,16 +1,26 @@
 use crate::{
+    caption::{Caption, MEDIA_CAPTION_LIMIT},
     comments::global_comments,
+    config::global_config,
+    context::MessageContext,
+    download::MediaInfo,
     error::{Error, Result},
+    metrics::metrics,
+    tr,
 };
 use capitalize::Capitalize;
 use std::{
     ffi::OsStr,
     fmt::Display,
     path::{Path, PathBuf},
+    time::Instant,
+};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, MessageEntityKind},
 };
-use teloxide::{prelude::*, types::InputFile};
 use tokio::{fs::File, io::AsyncReadExt};
-use tracing::{error, info, warn};
+use tracing::{error, info, instrument, warn};
 
 pub const VIDEO_EXTSTENSIONS: &[&str] = &["mp4", "webm", "mov", "mkv", "avi", "m4v", "3gp"];
 pub const IMAGE_EXTSTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
@@ -105,23 +115,59 @@ pub async fn detect_media_kind_async(path: &Path) -> MediaKind {
 
 /// Given a path, send it to chat as photo or video depending on detected kind.
 ///
+/// The media replies to the source message in its forum topic, with the
+/// context's attribution on the first line of the caption (see [`Caption`]). It is hidden behind
+/// a spoiler when the context asks for it or `info` says it is age-restricted.
+///
 /// # Errors
 ///
 /// Returns an `Error::UnknownMediaKind` if sending fails or the media kind is unknown.
+#[instrument(skip(bot, ctx, path), fields(chat_id = %ctx.chat_id, path = %path.display()))]
 pub async fn send_media_from_path(
     bot: &Bot,
-    chat_id: ChatId,
+    ctx: &MessageContext,
     path: PathBuf,
     kind: MediaKind,
+    info: Option<&MediaInfo>,
 ) -> Result<()> {
-    let caption = global_comments().build_caption();
+    let spoiler = ctx.spoiler || info.is_some_and(MediaInfo::is_age_restricted);
+    let mut caption_ctx = info.map_or_else(
+        || ctx.caption.clone(),
+        |info| ctx.caption.clone().with_media(info),
+    );
+    caption_ctx.kind = Some(kind);
+    let comment = global_comments().build_caption(&caption_ctx);
+    let caption = Caption::compose(&caption_ctx, comment, ctx.attribution.clone())
+        .render_configured(MEDIA_CAPTION_LIMIT);
+    let parse_mode = global_config().caption.format.parse_mode();
+    if let Ok(meta) = tokio::fs::metadata(&path).await {
+        #[allow(clippy::cast_precision_loss)]
+        metrics().file_size.observe(meta.len() as f64);
+    }
     let input = InputFile::file(path);
 
     macro_rules! send_msg {
         ($request_expr:expr) => {{
             let mut request = $request_expr;
             request = request.caption(caption);
-            match request.await {
+            if let Some(mode) = parse_mode {
+                request = request.parse_mode(mode);
+            }
+            if let Some(thread_id) = ctx.thread_id {
+                request = request.message_thread_id(thread_id);
+            }
+            if let Some(reply) = ctx.reply_parameters() {
+                request = request.reply_parameters(reply);
+            }
+            if spoiler {
+                request = request.has_spoiler(true);
+            }
+            let started = Instant::now();
+            let result = request.await;
+            metrics()
+                .upload_duration
+                .observe(started.elapsed().as_secs_f64());
+            match result {
                 Ok(message) => info!(message_id = message.id.to_string(), "{} sent", kind),
                 Err(e) => {
                     error!("Failed to send {}: {e}", kind.to_str());
@@ -132,11 +178,10 @@ pub async fn send_media_from_path(
     }
 
     match kind {
-        MediaKind::Video => send_msg!(bot.send_video(chat_id, input)),
-        MediaKind::Image => send_msg!(bot.send_photo(chat_id, input)),
+        MediaKind::Video => send_msg!(bot.send_video(ctx.chat_id, input)),
+        MediaKind::Image => send_msg!(bot.send_photo(ctx.chat_id, input)),
         MediaKind::Unknown => {
-            bot.send_message(chat_id, "No supported media found")
-                .await?;
+            ctx.send_message(bot, ctx.message("no-media")).await?;
             error!("No supported media found");
             return Err(Error::UnknownMediaKind);
         }
@@ -145,6 +190,60 @@ pub async fn send_media_from_path(
     Ok(())
 }
 
+/// Credit line used in replace mode: "Shared by @user: <text around the link>"
+/// in `lang`.
+#[must_use]
+pub fn attribution(msg: &Message, url: &str, lang: &str) -> String {
+    let poster = msg.from.as_ref().map_or_else(
+        || {
+            msg.sender_chat
+                .as_ref()
+                .and_then(|chat| chat.title())
+                .unwrap_or("someone")
+                .to_owned()
+        },
+        |user| user.mention().unwrap_or_else(|| user.full_name()),
+    );
+    let extra = msg.text().unwrap_or_default().replacen(url, "", 1);
+    let extra = extra.trim();
+
+    if extra.is_empty() {
+        tr!(lang, "shared-by", user = poster)
+    } else {
+        tr!(
+            lang,
+            "shared-by-with-text",
+            user = poster,
+            text = extra.to_owned(),
+        )
+    }
+}
+
+/// Whether the link at `url` should be relayed behind a spoiler: it is
+/// (partly) inside a spoiler entity or the message is tagged `nsfw`.
+#[must_use]
+pub fn wants_spoiler(msg: &Message, url: &str) -> bool {
+    let text = msg.text().unwrap_or_default();
+    let tagged_nsfw = text
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.eq_ignore_ascii_case("nsfw"));
+    if tagged_nsfw {
+        return true;
+    }
+
+    let Some(start) = text.find(url) else {
+        return false;
+    };
+    let url_range = start..start + url.len();
+    msg.parse_entities().is_some_and(|entities| {
+        entities.iter().any(|entity| {
+            *entity.kind() == MessageEntityKind::Spoiler
+                && entity.start() < url_range.end
+                && url_range.start < entity.end()
+        })
+    })
+}
+
 impl AsRef<str> for MediaKind {
     fn as_ref(&self) -> &str {
         self.to_str()
@@ -171,6 +270,35 @@ mod tests {
         );
     }
 
+    fn text_message(text: &str, entities: &str) -> Message {
+        let json = format!(
+            r#"{{"message_id":1,"date":0,"chat":{{"id":1,"type":"private","first_name":"a"}},"text":{text:?},"entities":{entities}}}"#
+        );
+        serde_json::from_str(&json).expect("valid message json")
+    }
+
+    #[test]
+    fn spoiler_entity_or_keyword() {
+        let url = "https://x.com/a/status/1";
+        let plain = text_message(&format!("look {url}"), "[]");
+        assert!(!wants_spoiler(&plain, url));
+
+        let hidden = text_message(
+            &format!("look {url}"),
+            r#"[{"type":"spoiler","offset":5,"length":24}]"#,
+        );
+        assert!(wants_spoiler(&hidden, url));
+
+        let other_spoiler = text_message(
+            &format!("look {url}"),
+            r#"[{"type":"spoiler","offset":0,"length":4}]"#,
+        );
+        assert!(!wants_spoiler(&other_spoiler, url));
+
+        let tagged = text_message(&format!("{url} #NSFW"), "[]");
+        assert!(wants_spoiler(&tagged, url));
+    }
+
     #[test]
     fn media_kind_case_insensitive() {
         assert_eq!(detect_media_kind(Path::new("VIDEO.MP4")), MediaKind::Video);